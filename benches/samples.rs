@@ -0,0 +1,33 @@
+use bpcsync::{cst, SignalSamples, SignalWave, BPC};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+// one second of samples at each rate, with no audio output involved
+fn one_second(c: &mut Criterion) {
+    let mut group = c.benchmark_group("one second");
+    for sample_rate in [44100, 96000] {
+        group.throughput(Throughput::Elements(sample_rate as u64));
+        group.bench_function(BenchmarkId::new("SignalWave", sample_rate), |b| {
+            let mut wave = SignalWave::new(BPC::new(), sample_rate, cst());
+            b.iter(|| {
+                for _ in 0..sample_rate {
+                    black_box(wave.next());
+                }
+            })
+        });
+        // the samples the audio callback pulls, reading the atomics published
+        // by the update thread
+        group.bench_function(BenchmarkId::new("SignalSamples", sample_rate), |b| {
+            let mut wave = SignalSamples::with_sample_rate(sample_rate).unwrap();
+            b.iter(|| {
+                for _ in 0..sample_rate {
+                    black_box(wave.next());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, one_second);
+criterion_main!(benches);
@@ -0,0 +1,260 @@
+//! Measures the pulses of rendered or recorded samples, to check what a
+//! receiver would make of them.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+// envelope window, 1ms
+const WINDOW_MS: usize = 1;
+
+/// Recovers the width of the power reduction starting each second of
+/// `samples`, the inverse of what a [`TimeSignal`](crate::TimeSignal)
+/// modulates.
+///
+/// `samples` must start on a second boundary. The envelope is the peak of
+/// every 1 ms window; a second yields the time in ms between the envelope
+/// first falling below half of the second's peak and rising back above it,
+/// or `None` when it never falls. Measuring between the two half-amplitude
+/// points keeps ramped edges from skewing the width. The widths are as
+/// measured, to the ms, and left for [`BPC::decode_tolerant`] to snap to a
+/// symbol. A trailing partial second is ignored.
+///
+/// [`BPC::decode_tolerant`]: crate::BPC::decode_tolerant
+pub fn pulse_widths(samples: &[f32], sample_rate: u32) -> Vec<Option<u32>> {
+    let window = (sample_rate as usize * WINDOW_MS / 1000).max(1);
+    samples
+        .chunks_exact(sample_rate as usize)
+        .map(|second| {
+            let peak = second.iter().fold(0f32, |m, s| m.max(s.abs()));
+            let high: Vec<bool> = second
+                .chunks(window)
+                .map(|w| w.iter().any(|s| s.abs() > peak / 2.))
+                .collect();
+            // only a reduction starting the second counts
+            let fall = high.iter().take(10).position(|&h| !h)?;
+            let rise = fall + high[fall..].iter().position(|&h| h)?;
+            Some(millis((rise - fall) * window, sample_rate))
+        })
+        .collect()
+}
+
+// length of `samples` samples in ms, to the nearest one
+fn millis(samples: usize, sample_rate: u32) -> u32 {
+    (samples as f64 * 1000. / sample_rate as f64).round() as u32
+}
+
+// how far from a second after the previous one the next may start in a
+// recording, 50ms
+const TRACK_MS: usize = 50;
+
+/// The seconds found in a recording by [`recorded_widths`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    /// Samples before the start of the first second.
+    pub offset: usize,
+    /// Width in ms of the power reduction starting each second, as
+    /// [`pulse_widths`].
+    pub widths: Vec<Option<u32>>,
+}
+
+/// Like [`pulse_widths`], but for a recording started at any time, from an
+/// output whose seconds are not exactly `sample_rate` samples long.
+///
+/// Each second starts where its envelope falls below half of the peak of
+/// the whole recording, if it does within 50 ms of a second after the start
+/// of the previous one, so that jitter and drift between the output and
+/// the recorder do not add up. A second without a fall, like a frame
+/// marker, lasts exactly a second. `None` when the envelope never falls.
+pub fn recorded_widths(samples: &[f32], sample_rate: u32) -> Option<Recording> {
+    let window = (sample_rate as usize * WINDOW_MS / 1000).max(1);
+    let peak = samples.iter().fold(0f32, |m, s| m.max(s.abs()));
+    let high: Vec<bool> = samples
+        .chunks(window)
+        .map(|w| w.iter().any(|s| s.abs() > peak / 2.))
+        .collect();
+    // from the last window above half, which a ramped edge straddles
+    let falls: Vec<usize> = (1..high.len())
+        .filter(|&i| high[i - 1] && !high[i])
+        .map(|i| (i - 1) * window)
+        .collect();
+    let second = sample_rate as usize;
+    let tolerance = second * TRACK_MS / 1000;
+
+    // whole seconds before the first fall, such as a marker, count too, and
+    // so does one starting a few ms before the recording
+    let offset = match falls.first()? % second {
+        o if second - o < second / 200 => 0,
+        o => o,
+    };
+    let mut widths = Vec::new();
+    let mut falls = falls.into_iter().peekable();
+    let mut start = offset;
+    // the last second may end a little past the recording
+    while start + second <= samples.len() + tolerance {
+        while falls.peek().is_some_and(|&f| f + tolerance < start) {
+            falls.next();
+        }
+        let fall = falls.next_if(|&f| f <= start + tolerance);
+        let width = fall.and_then(|fall| {
+            let end = ((fall + second) / window).min(high.len());
+            let rise = (fall / window + 1..end).find(|&i| high[i])? * window;
+            Some(millis(rise - fall, sample_rate))
+        });
+        widths.push(width);
+        start = fall.unwrap_or(start) + second;
+    }
+    Some(Recording { offset, widths })
+}
+
+/// Reads the first channel of a WAV file, integer or float, as samples
+/// within -1 to 1, along with its sample rate. The other channels of the
+/// output only repeat the first one.
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let reader = hound::WavReader::open(path)
+        .with_context(|| format!("unable to open {}", path.display()))?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let first = samples.into_iter().step_by(spec.channels.max(1) as usize);
+    Ok((first.collect(), spec.sample_rate))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{render, TimeSignal, BPC, WWVB};
+    use chrono::{FixedOffset, TimeZone};
+    use std::time::Duration;
+
+    // measured to within the 1ms window, and never onto another symbol
+    fn close(measured: Option<u32>, width: Option<u32>) -> bool {
+        match (measured, width) {
+            (Some(m), Some(w)) => m.abs_diff(w) <= 2,
+            (m, w) => m == w,
+        }
+    }
+
+    #[test]
+    fn pulse_widths_test() {
+        let start = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2023, 8, 20, 9, 15, 0)
+            .unwrap();
+        for signal in [&BPC::new() as &dyn TimeSignal, &WWVB::new()] {
+            let samples: Vec<f32> = render(signal, start, Duration::from_secs(1)).collect();
+            let widths = pulse_widths(&samples, 44100);
+            assert_eq!(widths.len(), signal.frame_len_secs() as usize);
+            for (i, width) in widths.into_iter().enumerate() {
+                let t = start + chrono::Duration::seconds(i as i64);
+                assert!(close(width, signal.symbol_width(t)), "{width:?} at {t}");
+            }
+        }
+    }
+
+    #[test]
+    fn recorded_widths_test() {
+        let start = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2023, 8, 20, 9, 15, 0)
+            .unwrap();
+        let sr = 48000;
+        let bpc = BPC::new();
+        let samples = BPC::render_samples(start, 2, sr, 12000.);
+        // the widths of the seconds from `from` on
+        let expected = |widths: &[Option<u32>], from: i64| {
+            widths.iter().enumerate().all(|(i, &width)| {
+                let t = start + chrono::Duration::seconds(from + i as i64);
+                close(width, bpc.symbol_width(t))
+            })
+        };
+
+        let exact = recorded_widths(&samples, sr).unwrap();
+        assert_eq!(exact.offset, 0);
+        assert_eq!(exact.widths.len(), 40);
+        assert!(expected(&exact.widths, 0));
+
+        // 300 ms into the second, every second a little off its length
+        let mut jittery = Vec::new();
+        for (i, second) in samples.chunks_exact(sr as usize).enumerate().skip(1) {
+            let skip = if i == 1 { sr as usize * 3 / 10 } else { 0 };
+            // cut short, or stretched with more of the full carrier ending it
+            let len = [sr as usize, sr as usize * 98 / 100, sr as usize * 102 / 100][i % 3];
+            let (body, tail) = match len.checked_sub(sr as usize) {
+                Some(more) => (second, &second[second.len() - more..]),
+                None => (&second[..len], &[][..]),
+            };
+            jittery.extend(&body[skip..]);
+            jittery.extend(tail);
+        }
+        let recording = recorded_widths(&jittery, sr).unwrap();
+        // the rest of the 980 ms of the first second
+        assert!(recording.offset.abs_diff(sr as usize * 68 / 100) < sr as usize / 100);
+        assert!(expected(&recording.widths, 2));
+        assert!(recording.widths.len() >= 36);
+
+        // after a marker, left as measured off any symbol
+        let second = |reduced_ms: usize| {
+            (0..sr as usize).map(move |i| match i < reduced_ms * sr as usize / 1000 {
+                true => 0.,
+                false => (i as f32 * 0.25 * std::f32::consts::TAU).sin(),
+            })
+        };
+        let off: Vec<f32> = second(0).chain(second(349)).chain(second(160)).collect();
+        let widths = recorded_widths(&off, sr).unwrap().widths;
+        assert_eq!(widths[0], None);
+        assert!(widths[1].unwrap().abs_diff(349) <= 1 && widths[2].unwrap().abs_diff(160) <= 1);
+
+        assert_eq!(recorded_widths(&[0.5; 4800], sr), None);
+    }
+
+    #[test]
+    fn read_wav_test() {
+        let path = std::env::temp_dir().join("bpcsync_read_wav_test.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for (left, right) in [(i16::MAX, 0), (i16::MIN, 0), (0, i16::MAX)] {
+            writer.write_sample(left).unwrap();
+            writer.write_sample(right).unwrap();
+        }
+        writer.finalize().unwrap();
+        let (samples, sample_rate) = read_wav(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sample_rate, 22050);
+        assert_eq!(samples.len(), 3);
+        assert!((samples[0] - 1.).abs() < 1e-4 && samples[1] == -1. && samples[2] == 0.);
+
+        assert!(read_wav(&path).is_err());
+    }
+
+    #[test]
+    fn render_samples_test() {
+        let start = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 2, 29, 23, 59, 40)
+            .unwrap();
+        let samples = BPC::render_samples(start, 2, 48000, 68500. / 7.);
+        let widths = pulse_widths(&samples, 48000);
+        let frame_len = BPC::new().frame_len_secs();
+        assert_eq!(widths.len(), 2 * frame_len as usize);
+        // across midnight into march 1st
+        for (i, frame) in widths.chunks_exact(frame_len as usize).enumerate() {
+            let expected = start + chrono::Duration::seconds((frame_len * i as u32) as i64);
+            let decoded = BPC::decode_tolerant(frame.try_into().unwrap(), 40).unwrap();
+            assert_eq!(decoded.time, expected);
+            assert!(decoded.max_error_ms <= 2, "{decoded:?}");
+        }
+    }
+}
@@ -1,21 +1,174 @@
-use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
-use rodio::Source;
-use std::{
-    f32::consts::PI,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
-    },
-    thread,
-    time::Duration,
+use crate::{
+    core::{date_check, time_check, Fields, BPC_FREQ, CODE_WIDTHS, FRAME_LEN, YEARS},
+    wave, SignalWave, TimeSignal,
 };
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{DateTime, Datelike, FixedOffset, TimeZone, Timelike, Utc};
+use std::{path::Path, time::Duration};
 
-type ZonedDateTime = DateTime<FixedOffset>;
+// normally speakers only produce sound frequency under 20khz
+pub(crate) const DEFAULT_CARRIER: f32 = (BPC_FREQ / 5) as f32;
+
+pub type ZonedDateTime = DateTime<FixedOffset>;
+
+/// Returns the current time in `offset`.
+///
+/// ```
+/// let utc = bpcsync::now_in(chrono::FixedOffset::east_opt(0).unwrap());
+/// assert_eq!(utc.offset().local_minus_utc(), 0);
+/// ```
+pub fn now_in(offset: FixedOffset) -> ZonedDateTime {
+    Utc::now().with_timezone(&offset)
+}
+
+/// Returns the current time in China Standard Time, the time BPC
+/// transmits.
+///
+/// ```
+/// use bpcsync::{TimeSignal, BPC};
+///
+/// let now = bpcsync::cst();
+/// assert_eq!(now.offset().local_minus_utc(), 8 * 3600);
+/// // what is being transmitted right now
+/// println!("{}", BPC::new().describe(now));
+/// ```
 pub fn cst() -> ZonedDateTime {
+    now_in(cst_offset())
+}
+
+pub(crate) fn cst_offset() -> FixedOffset {
     // china standard time
-    Utc::now().with_timezone(&FixedOffset::east_opt(8 * 3600).unwrap())
+    FixedOffset::east_opt(8 * 3600).unwrap()
+}
+
+// what each fragment of a frame carries
+const FRAGMENT_NAMES: [&str; FRAME_LEN] = [
+    "frame marker",
+    "second",
+    "reserved",
+    "hour high",
+    "hour low",
+    "minute high",
+    "minute middle",
+    "minute low",
+    "weekday high",
+    "weekday low",
+    "am/pm & check",
+    "day high",
+    "day middle",
+    "day low",
+    "month high",
+    "month low",
+    "year high",
+    "year middle",
+    "year low",
+    "year highest & check",
+];
+
+/// One transmitted frame: the codes of its 20 fragments and when it starts.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frame {
+    pub start: ZonedDateTime,
+    /// Index `i` holds the code of fragment `i`, `None` for the marker.
+    pub codes: [Option<u8>; FRAME_LEN],
+}
+
+/// A frame recovered by [`BPC::decode_tolerant`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decoded {
+    /// Start of the frame, in China Standard Time.
+    pub time: ZonedDateTime,
+    /// Largest distance in ms of a measured width from its nominal one.
+    pub max_error_ms: u32,
+}
+
+/// Length of the power reduction carrying a 2-bit code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PulseWidth {
+    W100,
+    W200,
+    W300,
+    W400,
+}
+
+impl PulseWidth {
+    /// Width in ms, as listed in [`CODE_WIDTHS`].
+    pub fn millis(&self) -> u32 {
+        CODE_WIDTHS[self.code() as usize].1
+    }
+
+    pub fn from_millis(ms: u32) -> Option<Self> {
+        CODE_WIDTHS
+            .iter()
+            .find(|&&(_, width)| width == ms)
+            .map(|&(code, _)| PulseWidth::from_code(code))
+    }
+
+    /// Snaps `ms` to the nearest width if it is within `tolerance` ms of it,
+    /// returning the width and how far `ms` is from it.
+    pub fn nearest(ms: u32, tolerance: u32) -> Option<(Self, u32)> {
+        let code = (ms.saturating_sub(50) / 100).min(3) as u8;
+        let width = PulseWidth::from_code(code);
+        let error = ms.abs_diff(width.millis());
+        (error <= tolerance).then_some((width, error))
+    }
+
+    /// The code carried, in `0..=3`.
+    pub fn code(&self) -> u8 {
+        match self {
+            PulseWidth::W100 => 0b00,
+            PulseWidth::W200 => 0b01,
+            PulseWidth::W300 => 0b10,
+            PulseWidth::W400 => 0b11,
+        }
+    }
+
+    fn from_code(code: u8) -> Self {
+        match code {
+            0b00 => PulseWidth::W100,
+            0b01 => PulseWidth::W200,
+            0b10 => PulseWidth::W300,
+            0b11 => PulseWidth::W400,
+            _ => unreachable!("unreachable code"),
+        }
+    }
 }
 
+/// The BPC time code broadcast on 68.5 kHz from Shangqiu, China.
+///
+/// A frame lasts 20 seconds and carries the China Standard Time of its
+/// start. The frame marker at seconds 0, 20 and 40 is a whole second of full
+/// carrier, and fragment 1 right after it tells which third of the minute
+/// the frame is, so a marker followed by `00` is how a receiver finds the
+/// minute. The spec defines no other minute marker.
+///
+/// Computing the code of a second, and rendering a frame of it into a
+/// buffer without any clock or audio output:
+///
+/// ```
+/// use bpcsync::{TimeSignal, BPC};
+/// use chrono::{FixedOffset, TimeZone};
+///
+/// let bpc = BPC::new();
+/// let t = FixedOffset::east_opt(8 * 3600)
+///     .unwrap()
+///     .with_ymd_and_hms(2023, 8, 20, 9, 15, 7)
+///     .unwrap();
+/// // minute low: 15 & 0b11
+/// assert_eq!(bpc.code_at(t), Some(0b11));
+/// assert_eq!(bpc.signal_width_ms(t), Some(400));
+/// assert_eq!(bpc.frame(t)[0], None);
+///
+/// let samples = BPC::render_samples(t, 1, 48000, 12000.);
+/// assert_eq!(samples.len(), 20 * 48000);
+/// // and back
+/// let widths = bpcsync::analysis::pulse_widths(&samples, 48000);
+/// let frame = widths[..20].try_into().unwrap();
+/// assert_eq!(BPC::decode_tolerant(frame, 40).unwrap().time, bpc.frame_start(t));
+/// ```
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Default)]
 pub struct BPC {}
 
 impl BPC {
@@ -23,267 +176,527 @@ impl BPC {
         Self {}
     }
 
-    // signal_width in ms
-    pub fn signal_width(&self, t: ZonedDateTime) -> Option<u32> {
-        match self.code(t) {
-            Some(0b00) => Some(100),
-            Some(0b01) => Some(200),
-            Some(0b10) => Some(300),
-            Some(0b11) => Some(400),
-            None => None,
-            _ => {
-                unreachable!()
+    /// Returns the 2-bit code transmitted during the second of `t`.
+    ///
+    /// The frame is 20 seconds long and indexed by `second % 20`. Fragment 0
+    /// is the frame marker and carries no code, so it returns `None`; every
+    /// other fragment yields a value in `0..=3`. Outside of the
+    /// [`YEARS`](crate::YEARS) a frame can carry, every fragment is `None`.
+    pub fn code_at(&self, t: ZonedDateTime) -> Option<u8> {
+        self.code(t)
+    }
+
+    /// Like [`BPC::code_at`] for a UTC instant, encoded in China Standard
+    /// Time as BPC transmits it.
+    pub fn code_utc(&self, t: DateTime<Utc>) -> Option<u8> {
+        self.code_at(t.with_timezone(&cst_offset()))
+    }
+
+    /// Returns the codes of the whole 20-second frame containing `t`.
+    ///
+    /// The frame starts at the aligned boundary where `second % 20 == 0`, so
+    /// index `i` of the result is the code transmitted at fragment `i`.
+    pub fn frame(&self, t: ZonedDateTime) -> [Option<u8>; FRAME_LEN] {
+        let start = self.frame_start(t);
+        let mut frame = [None; FRAME_LEN];
+        for (i, code) in frame.iter_mut().enumerate() {
+            *code = self.code(start + chrono::Duration::seconds(i as i64));
+        }
+        frame
+    }
+
+    /// Like [`BPC::frame`] for a UTC instant, encoded in China Standard Time.
+    pub fn frame_utc(&self, t: DateTime<Utc>) -> [Option<u8>; FRAME_LEN] {
+        self.frame(t.with_timezone(&cst_offset()))
+    }
+
+    /// Returns the second, code and width in ms of each of the 60 seconds of
+    /// the minute containing `t`, its three frames in a row.
+    ///
+    /// The data repeats in every frame, only the second fragment and the
+    /// time check bit telling them apart.
+    pub fn minute_frame(
+        &self,
+        t: ZonedDateTime,
+    ) -> impl Iterator<Item = (u32, Option<u8>, Option<u32>)> + '_ {
+        let start = self.minute_start(t);
+        (0..60).map(move |second| {
+            let t = start + chrono::Duration::seconds(second as i64);
+            (second, self.code(t), self.signal_width_ms(t))
+        })
+    }
+
+    /// Like [`BPC::frame`], but keeps the start of the frame alongside.
+    pub fn frame_struct(&self, t: ZonedDateTime) -> Frame {
+        Frame {
+            start: self.frame_start(t),
+            codes: self.frame(t),
+        }
+    }
+
+    /// Renders the BPC waveform into a 16-bit PCM WAV file, see
+    /// [`render_wav`](crate::render_wav).
+    pub fn render_wav(path: &Path, start: ZonedDateTime, duration: Duration) -> Result<()> {
+        wave::render_wav(&BPC::new(), path, start, duration)
+    }
+
+    /// Renders `frames` whole frames of the BPC waveform on a `carrier` Hz
+    /// tone, from the frame containing `start`. Like [`render`](crate::render)
+    /// it reads no clock and spawns no thread, so the same arguments always
+    /// give the same samples.
+    pub fn render_samples(
+        start: ZonedDateTime,
+        frames: u32,
+        sample_rate: u32,
+        carrier: f32,
+    ) -> Vec<f32> {
+        let bpc = BPC::new();
+        let start = bpc.frame_start(start);
+        let len = frames as usize * bpc.frame_len_secs() as usize * sample_rate as usize;
+        SignalWave::new(bpc, sample_rate, start)
+            .with_carrier(carrier)
+            .take(len)
+            .collect()
+    }
+
+    /// Describes what is transmitted during the second of `t`, e.g.
+    /// `09:15:07 [ 7] minute low = 11 (400ms)`.
+    pub fn describe(&self, t: ZonedDateTime) -> String {
+        let fragment = (t.second() % self.frame_len_secs()) as usize;
+        let name = FRAGMENT_NAMES[fragment];
+        let time = t.format("%H:%M:%S");
+        match (self.code(t), self.signal_width_ms(t)) {
+            (Some(code), Some(width)) => {
+                format!("{time} [{fragment:>2}] {name} = {code:02b} ({width}ms)")
             }
+            _ => format!("{time} [{fragment:>2}] {name}, no pulse"),
         }
     }
 
+    /// Returns the power reduction starting the second of `t`, or `None` for
+    /// the frame marker.
+    pub fn signal_width(&self, t: ZonedDateTime) -> Option<PulseWidth> {
+        self.code(t).map(PulseWidth::from_code)
+    }
+
+    /// [`BPC::signal_width`] in ms.
+    pub fn signal_width_ms(&self, t: ZonedDateTime) -> Option<u32> {
+        self.signal_width(t).map(|w| w.millis())
+    }
+
+    /// Recovers the time from the pulse widths of one frame, the inverse of
+    /// [`BPC::signal_width_ms`] over [`BPC::frame`].
+    ///
+    /// `widths[0]` must be the marker (`None`) and every other entry one of
+    /// the 100/200/300/400 ms widths. Both check bits are verified. The
+    /// returned time is the start of the frame, in China Standard Time.
+    pub fn decode(widths: &[Option<u32>; FRAME_LEN]) -> Result<ZonedDateTime> {
+        ensure!(widths[0].is_none(), "fragment 0 is not a frame marker");
+        let mut codes = [0u32; FRAME_LEN];
+        for (i, width) in widths.iter().enumerate().skip(1) {
+            codes[i] = match width.map(|w| (w, PulseWidth::from_millis(w))) {
+                Some((_, Some(pulse))) => pulse.code() as u32,
+                Some((w, None)) => bail!("unexpected width {w}ms at fragment {i}"),
+                None => bail!("missing pulse at fragment {i}"),
+            };
+        }
+
+        let second = match codes[1] {
+            v @ 0..=2 => v * FRAME_LEN as u32,
+            v => bail!("invalid second code {v:#04b}"),
+        };
+        let hour = codes[3] << 2 | codes[4];
+        let minute = codes[5] << 4 | codes[6] << 2 | codes[7];
+        let weekday = codes[8] << 2 | codes[9];
+        let pm = codes[10] & 0b10 != 0;
+        let day = codes[11] << 4 | codes[12] << 2 | codes[13];
+        let month = codes[14] << 2 | codes[15];
+        let year = (codes[19] >> 1) << 6 | codes[16] << 4 | codes[17] << 2 | codes[18];
+
+        ensure!(
+            time_check([0b00, 0b01, 0b11][codes[1] as usize], hour, minute, weekday)
+                == codes[10] as u8 & 0b1,
+            "time check bit mismatch"
+        );
+        ensure!(
+            date_check(day, month, year) == codes[19] as u8 & 0b1,
+            "date check bit mismatch"
+        );
+        ensure!(
+            year <= (YEARS.end() - YEARS.start()) as u32,
+            "year code {year} is past {}",
+            YEARS.end()
+        );
+        // the 4 bits have room for 12 - 15, which no 12-hour clock reads
+        ensure!(hour <= 11, "hour code {hour} is past 11");
+
+        let hour = hour + if pm { 12 } else { 0 };
+        let t = cst_offset()
+            .with_ymd_and_hms(2000 + year as i32, month, day, hour, minute, second)
+            .single()
+            .with_context(|| {
+                format!("invalid time 20{year:02}-{month:02}-{day:02} {hour:02}:{minute:02}")
+            })?;
+        ensure!(
+            t.weekday().number_from_monday() == weekday,
+            "weekday {weekday} does not match {}",
+            t.date_naive()
+        );
+        Ok(t)
+    }
+
+    /// Like [`BPC::decode`], but for measured widths: each one within
+    /// `tolerance` ms of a nominal width, e.g. 40, snaps to it. The
+    /// tolerance must stay under 50 ms so that the bands do not overlap.
+    pub fn decode_tolerant(widths: &[Option<u32>; FRAME_LEN], tolerance: u32) -> Result<Decoded> {
+        ensure!(tolerance < 50, "tolerance {tolerance}ms must be under 50ms");
+        let mut snapped = [None; FRAME_LEN];
+        let mut max_error_ms = 0;
+        for (i, width) in widths.iter().enumerate() {
+            let Some(w) = *width else { continue };
+            let (pulse, error) = PulseWidth::nearest(w, tolerance).with_context(|| {
+                format!("width {w}ms at fragment {i} is not within {tolerance}ms of any symbol")
+            })?;
+            snapped[i] = Some(pulse.millis());
+            max_error_ms = max_error_ms.max(error);
+        }
+        let time = BPC::decode(&snapped)?;
+        Ok(Decoded { time, max_error_ms })
+    }
+
     fn code(&self, now: ZonedDateTime) -> Option<u8> {
-        let year = now.year() - 2000;
-        let month = now.month();
-        let day = now.day();
-        let weekday = now.weekday().number_from_monday();
+        // BPC transmits a 12-hour clock as 0 - 11 plus the am/pm bit of
+        // fragment 10, so 12 AM (midnight) and 12 PM (noon) are both hour 0
         let (pm, hour) = now.hour12();
-        let minute = now.minute();
-        let second = now.second();
-
-        let fragment = second % 20;
-        match fragment {
-            0 => {
-                // empty
-                None
-            }
-            1 => {
-                // seconds, 01 / 21 / 41
-                let v: u32 = match second {
-                    1 => 0,
-                    21 => 1,
-                    41 => 2,
-                    _ => {
-                        unreachable!("unreachable seconds")
-                    }
-                };
-                Some(v as u8)
-            }
-            2 => {
-                // reserved
-                Some(0)
-            }
-            3 => {
-                // hour high
-                let high = hour >> 2;
-                Some(high as u8)
-            }
-            4 => {
-                // hour low
-                let low = hour & 0b11;
-                Some(low as u8)
-            }
-            5 => {
-                // minute high
-                let high = minute >> 4;
-                Some(high as u8)
-            }
-            6 => {
-                // minute middle
-                let middle = (minute >> 2) & 0b11;
-                Some(middle as u8)
-            }
-            7 => {
-                // minute low
-                let low = minute & 0b11;
-                Some(low as u8)
-            }
-            8 => {
-                // weekday high
-                let high = weekday >> 2;
-                Some(high as u8)
-            }
-            9 => {
-                // weekday low
-                let low = weekday & 0b11;
-                Some(low as u8)
-            }
-            10 => {
-                // check & am/pm
-                let mut v: u8 = if pm { 0b10 } else { 0b00 };
-                // second range: 0 - 59
-                let s = match second {
-                    1..=20 => 0b00,
-                    21..=40 => 0b01,
-                    41..=59 => 0b11,
-                    _ => {
-                        unreachable!()
-                    }
-                };
-                let c = vec![s, hour, minute, weekday]
-                    .into_iter()
-                    .reduce(|acc, e| acc + e.count_ones())
-                    .unwrap();
-                v |= if c % 2 == 0 { 0b0 } else { 0b1 };
-                Some(v)
-            }
-            11 => {
-                // day high
-                let high = day >> 4;
-                Some(high as u8)
-            }
-            12 => {
-                // day middle
-                let middle = (day >> 2) & 0b11;
-                Some(middle as u8)
-            }
-            13 => {
-                // day low
-                let low = day & 0b11;
-                Some(low as u8)
-            }
-            14 => {
-                // month high
-                let high = month >> 2;
-                Some(high as u8)
-            }
-            15 => {
-                // month low
-                let low = month & 0b11;
-                Some(low as u8)
-            }
-            16 => {
-                // year high
-                let high = (year >> 4) & 0b11;
-                Some(high as u8)
-            }
-            17 => {
-                // year middle
-                let middle = (year >> 2) & 0b11;
-                Some(middle as u8)
-            }
-            18 => {
-                // year low
-                let low = year & 0b11;
-                Some(low as u8)
-            }
-            19 => {
-                // check & year highest bit
-                let year_highest = (year >> 6) & 0b1;
-                let mut v = year_highest << 1;
-                let c = vec![day, month, (year & 0b111111) as u32]
-                    .into_iter()
-                    .reduce(|acc, e| acc + e.count_ones())
-                    .unwrap();
-                v |= if c % 2 == 0 { 0b0 } else { 0b1 };
-                Some(v as u8)
-            }
-            _ => {
-                unreachable!("unreachable fragment")
-            }
-        }
+        crate::core::code(&Fields {
+            // out of range years are turned down by the encoder
+            year: now.year().try_into().unwrap_or(0),
+            month: now.month() as u8,
+            day: now.day() as u8,
+            weekday: now.weekday().number_from_monday() as u8,
+            hour: (hour % 12) as u8,
+            pm,
+            minute: now.minute() as u8,
+            second: now.second() as u8,
+        })
     }
 }
 
-const BPC_FREQ: u32 = 68500;
-const SAMPLE_RATE: u32 = 44100; //48000;
+impl TimeSignal for BPC {
+    fn symbol_width(&self, t: ZonedDateTime) -> Option<u32> {
+        self.signal_width_ms(t)
+    }
+
+    fn carrier_hz(&self) -> f32 {
+        DEFAULT_CARRIER
+    }
 
-struct BPCWaveInner {
-    bpc: BPC,
-    num_samples: usize,
-    pivot: usize,
-    updating: Arc<AtomicBool>,
+    fn frame_len_secs(&self) -> u32 {
+        FRAME_LEN as u32
+    }
+
+    fn describe(&self, t: ZonedDateTime) -> String {
+        BPC::describe(self, t)
+    }
+
+    /// The low bit of fragments 10 and 19.
+    fn check_bit(&self, t: ZonedDateTime) -> Option<u8> {
+        match t.second() % self.frame_len_secs() {
+            10 | 19 => self.code(t).map(|code| code & 0b1),
+            _ => None,
+        }
+    }
 }
 
-impl BPCWaveInner {
-    pub fn new() -> Self {
-        Self {
-            bpc: BPC::new(),
-            num_samples: 0,
-            pivot: 0,
-            updating: Arc::new(AtomicBool::new(false)),
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wave::DEFAULT_SAMPLE_RATE;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> ZonedDateTime {
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(y, mo, d, h, mi, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn frame_test() {
+        let bpc = BPC::new();
+        for t in [
+            at(2023, 8, 20, 9, 15, 0),
+            at(2023, 8, 20, 9, 15, 7),
+            at(2023, 8, 20, 21, 59, 59),
+            at(2024, 2, 29, 0, 0, 33),
+        ] {
+            let frame = bpc.frame(t);
+            assert_eq!(frame[0], None);
+            assert_eq!(frame[2], Some(0));
+            assert_eq!(frame[(t.second() % 20) as usize], bpc.code_at(t));
         }
     }
 
-    pub fn update(&mut self, t: ZonedDateTime) {
-        self.updating.store(true, Ordering::SeqCst);
-        self.pivot = (self.bpc.signal_width(t).unwrap_or(0) * SAMPLE_RATE / 1000) as usize;
-        self.num_samples = 0;
-        self.updating.store(false, Ordering::SeqCst);
+    #[test]
+    fn utc_test() {
+        let bpc = BPC::new();
+        // the second one is already the next day in china
+        for cst in [at(2023, 8, 20, 9, 15, 7), at(2023, 8, 21, 0, 0, 3)] {
+            let utc = cst.with_timezone(&Utc);
+            assert_eq!(bpc.code_utc(utc), bpc.code_at(cst));
+            assert_eq!(bpc.frame_utc(utc), bpc.frame(cst));
+        }
+        let utc = Utc.with_ymd_and_hms(2023, 8, 20, 16, 0, 0).unwrap();
+        assert_ne!(bpc.frame_utc(utc), bpc.frame(utc.fixed_offset()));
     }
-}
 
-impl Iterator for BPCWaveInner {
-    type Item = f32;
+    #[test]
+    fn next_frame_start_test() {
+        let bpc = BPC::new();
+        let start = at(2023, 8, 20, 9, 15, 20);
+        assert_eq!(bpc.next_frame_start(start), start);
+        assert_eq!(bpc.next_frame_start(at(2023, 8, 20, 9, 15, 7)), start);
+        let t = at(2023, 8, 20, 9, 15, 0) + chrono::Duration::milliseconds(1);
+        assert_eq!(bpc.next_frame_start(t), start);
+        assert_eq!(
+            bpc.next_frame_start(at(2023, 8, 20, 9, 59, 41)),
+            at(2023, 8, 20, 10, 0, 0)
+        );
 
-    fn next(&mut self) -> Option<f32> {
-        if self.updating.load(Ordering::SeqCst) {
-            return Some(1.);
+        let minute = at(2023, 8, 20, 9, 15, 0);
+        for (second, frame, next_minute) in [
+            (0, 0, 0),
+            (1, 20, 60),
+            (19, 20, 60),
+            (20, 20, 60),
+            (59, 60, 60),
+        ] {
+            let t = minute + chrono::Duration::seconds(second);
+            let after = |s| minute + chrono::Duration::seconds(s);
+            assert_eq!(bpc.next_frame_start(t), after(frame), "second {second}");
+            assert_eq!(
+                bpc.next_minute_start(t),
+                after(next_minute),
+                "second {second}"
+            );
+            assert_eq!(bpc.minute_start(t), minute);
         }
+        // mid-second still counts as past the boundary
+        let late = minute + chrono::Duration::milliseconds(500);
+        assert_eq!(bpc.next_minute_start(late), at(2023, 8, 20, 9, 16, 0));
+    }
 
-        self.num_samples += 1;
+    fn widths(bpc: &BPC, t: ZonedDateTime) -> [Option<u32>; FRAME_LEN] {
+        let start = bpc.frame_start(t);
+        std::array::from_fn(|i| bpc.signal_width_ms(start + chrono::Duration::seconds(i as i64)))
+    }
 
-        let fc = BPC_FREQ / 5; // normally speakers only produce sound frequency under 20khz
-        let value = 2.0 * PI * fc as f32 * self.num_samples as f32 / SAMPLE_RATE as f32;
-        if self.num_samples >= self.pivot {
-            Some(value.sin())
-        } else {
-            Some(0.)
+    #[test]
+    fn decode_test() {
+        let bpc = BPC::new();
+        for t in [
+            at(2023, 8, 20, 9, 15, 0),
+            at(2023, 8, 20, 0, 30, 27),
+            at(2023, 12, 31, 12, 0, 45),
+            at(2024, 2, 29, 23, 59, 59),
+            at(2099, 1, 1, 13, 1, 20),
+        ] {
+            let start = t - chrono::Duration::seconds((t.second() % 20) as i64);
+            assert_eq!(BPC::decode(&widths(&bpc, t)).unwrap(), start);
         }
     }
-}
 
-pub struct BPCWave {
-    inner: Arc<Mutex<BPCWaveInner>>,
-}
+    #[test]
+    fn decode_tolerant_test() {
+        let bpc = BPC::new();
+        let t = at(2023, 8, 20, 9, 15, 0);
+        let mut w = widths(&bpc, t);
+        let jitter = [
+            0, 12, -30, 40, -40, 5, 0, -8, 21, 33, -1, 0, 17, -25, 9, 2, -39, 11, 0, 6,
+        ];
+        for (w, j) in w.iter_mut().zip(jitter) {
+            *w = w.map(|w| w.checked_add_signed(j).unwrap());
+        }
+        assert!(BPC::decode(&w).is_err());
+        let decoded = BPC::decode_tolerant(&w, 40).unwrap();
+        assert_eq!(decoded.time, t);
+        assert_eq!(decoded.max_error_ms, 40);
 
-impl BPCWave {
-    pub fn new() -> Self {
-        let inner = Arc::new(Mutex::new(BPCWaveInner::new()));
-        thread::spawn({
-            let inner = inner.clone();
-            move || loop {
-                {
-                    let now = cst();
-                    let delta = 1_000_000 - now.timestamp_subsec_micros();
-                    thread::sleep(Duration::from_micros(delta as u64));
-                }
+        w[4] = w[4].map(|w| w - 1);
+        let e = BPC::decode_tolerant(&w, 40).unwrap_err();
+        assert!(e.to_string().contains("fragment 4"), "{e}");
+        assert!(BPC::decode_tolerant(&w, 50).is_err());
+        assert_eq!(PulseWidth::nearest(460, 40), None);
+        assert_eq!(PulseWidth::nearest(62, 40), Some((PulseWidth::W100, 38)));
+    }
+
+    #[test]
+    fn decode_error_test() {
+        let bpc = BPC::new();
+        let valid = widths(&bpc, at(2023, 8, 20, 9, 15, 0));
+
+        let mut w = valid;
+        w[4] = Some(250);
+        assert!(BPC::decode(&w).is_err());
+
+        // flip a single minute bit so that only the check bit disagrees
+        let mut w = valid;
+        w[7] = w[7].map(|w| (((w / 100 - 1) ^ 0b1) + 1) * 100);
+        assert!(BPC::decode(&w).is_err());
+
+        let mut w = valid;
+        w[0] = Some(100);
+        assert!(BPC::decode(&w).is_err());
+
+        // 09:15 corrupted into hour 13, with a check bit that still agrees
+        let code = |w: Option<u32>| w.unwrap() / 100 - 1;
+        let mut w = valid;
+        w[3] = Some(400);
+        w[4] = Some(200);
+        assert_eq!(code(w[3]) << 2 | code(w[4]), 13);
+        let check = time_check(0, 13, 15, 7) as u32;
+        w[10] = Some(((code(w[10]) & 0b10 | check) + 1) * 100);
+        let err = BPC::decode(&w).unwrap_err();
+        assert!(err.to_string().contains("hour code 13"), "{err}");
+        let err = BPC::decode_tolerant(&w, 40).unwrap_err();
+        assert!(err.to_string().contains("hour code 13"), "{err}");
+    }
 
-                let now = cst();
-                inner.lock().unwrap().update(now);
+    #[test]
+    fn hour12_test() {
+        let bpc = BPC::new();
+        // (hour, minute) -> (hour high, hour low, pm)
+        for ((h, m), expected) in [
+            ((0, 0), (0b00, 0b00, 0)),
+            ((0, 59), (0b00, 0b00, 0)),
+            ((12, 0), (0b00, 0b00, 1)),
+            ((23, 59), (0b10, 0b11, 1)),
+        ] {
+            let frame = bpc.frame(at(2023, 8, 20, h, m, 0));
+            let codes = (
+                frame[3].unwrap(),
+                frame[4].unwrap(),
+                frame[10].unwrap() >> 1,
+            );
+            assert_eq!(codes, expected, "{h:02}:{m:02}");
+        }
+    }
+
+    #[test]
+    fn am_pm_test() {
+        let bpc = BPC::new();
+        // hour -> (hour high, hour low, pm)
+        for (h, expected) in [
+            (0, (0b00, 0b00, 0)),
+            (6, (0b01, 0b10, 0)),
+            (12, (0b00, 0b00, 1)),
+            (15, (0b00, 0b11, 1)),
+            (18, (0b01, 0b10, 1)),
+        ] {
+            // every third of the minute carries the same hour and pm bit
+            for s in [0u32, 20, 40] {
+                let code = |fragment| bpc.code_at(at(2023, 8, 20, h, 30, s + fragment)).unwrap();
+                let codes = (code(3), code(4), code(10) >> 1);
+                assert_eq!(codes, expected, "{h:02}:30:{s:02}");
             }
-        });
-        Self { inner }
+            let start = at(2023, 8, 20, h, 30, 0);
+            let widths = bpc
+                .frame(start)
+                .map(|c| c.map(|c| PulseWidth::from_code(c).millis()));
+            assert_eq!(BPC::decode(&widths).unwrap(), start);
+        }
     }
-}
 
-impl Iterator for BPCWave {
-    type Item = f32;
+    #[test]
+    fn pulse_width_test() {
+        let bpc = BPC::new();
+        let t = at(2023, 8, 20, 9, 15, 7);
+        assert_eq!(bpc.signal_width(t), Some(PulseWidth::W400));
+        assert_eq!(bpc.signal_width_ms(t), Some(400));
+        assert_eq!(bpc.signal_width(at(2023, 8, 20, 9, 15, 20)), None);
+        for code in 0..4 {
+            let width = PulseWidth::from_code(code);
+            assert_eq!(width.code(), code);
+            assert_eq!(PulseWidth::from_millis(width.millis()), Some(width));
+        }
+        assert_eq!(PulseWidth::from_millis(250), None);
+        for (code, width) in CODE_WIDTHS {
+            assert_eq!(PulseWidth::from_code(code).millis(), width);
+        }
+    }
 
-    fn next(&mut self) -> Option<f32> {
-        self.inner.lock().unwrap().next()
+    #[test]
+    fn minute_frame_test() {
+        let bpc = BPC::new();
+        let minute: Vec<_> = bpc.minute_frame(at(2023, 8, 20, 9, 15, 47)).collect();
+        assert_eq!(minute.len(), 60);
+        for (i, (second, code, width)) in minute.iter().enumerate() {
+            let t = at(2023, 8, 20, 9, 15, i as u32);
+            assert_eq!(*second, i as u32);
+            assert_eq!((*code, *width), (bpc.code_at(t), bpc.signal_width_ms(t)));
+        }
+        for (a, b) in minute.iter().zip(&minute[20..]) {
+            match a.0 % 20 {
+                1 => assert_ne!(a.1, b.1, "second {}", a.0),
+                // only the am/pm bit, the check bit covers the second fragment
+                10 => assert_eq!(a.1.map(|c| c & 0b10), b.1.map(|c| c & 0b10)),
+                _ => assert_eq!(a.1, b.1, "second {}", a.0),
+            }
+        }
     }
-}
 
-impl Source for BPCWave {
-    #[inline]
-    fn current_frame_len(&self) -> Option<usize> {
-        None
+    #[test]
+    fn frame_struct_test() {
+        let bpc = BPC::new();
+        let frame = bpc.frame_struct(at(2023, 8, 20, 9, 15, 7));
+        assert_eq!(frame.start, at(2023, 8, 20, 9, 15, 0));
+        assert_eq!(frame.codes, bpc.frame(frame.start));
     }
 
-    #[inline]
-    fn channels(&self) -> u16 {
-        1
+    #[cfg(feature = "serde")]
+    #[test]
+    fn frame_serde_test() {
+        let frame = BPC::new().frame_struct(at(2023, 8, 20, 9, 15, 7));
+        let json = serde_json::to_string(&frame).unwrap();
+        assert!(json.starts_with(r#"{"start":"2023-08-20T09:15:00+08:00","codes":[null,0,0,2,1"#));
+        assert_eq!(serde_json::from_str::<Frame>(&json).unwrap(), frame);
     }
 
-    #[inline]
-    fn sample_rate(&self) -> u32 {
-        SAMPLE_RATE
+    #[test]
+    fn render_samples_test() {
+        let start = at(2023, 8, 20, 9, 15, 7);
+        let samples = BPC::render_samples(start, 2, 48000, DEFAULT_CARRIER);
+        assert_eq!(samples.len(), 2 * 20 * 48000);
+
+        let samples = BPC::render_samples(start, 1, DEFAULT_SAMPLE_RATE, DEFAULT_CARRIER);
+        let rendered: Vec<f32> =
+            crate::render(BPC::new(), start, Duration::from_secs(20)).collect();
+        assert_eq!(samples, rendered);
     }
 
-    #[inline]
-    fn total_duration(&self) -> Option<std::time::Duration> {
-        None
+    #[test]
+    fn describe_test() {
+        let bpc = BPC::new();
+        assert_eq!(
+            bpc.describe(at(2023, 8, 20, 9, 15, 7)),
+            "09:15:07 [ 7] minute low = 11 (400ms)"
+        );
+        assert_eq!(
+            bpc.describe(at(2023, 8, 20, 9, 15, 20)),
+            "09:15:20 [ 0] frame marker, no pulse"
+        );
+    }
+
+    #[test]
+    fn render_wav_test() {
+        let path = std::env::temp_dir().join("bpcsync_render_wav_test.wav");
+        BPC::render_wav(&path, at(2023, 8, 20, 9, 15, 7), Duration::from_secs(5)).unwrap();
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, DEFAULT_SAMPLE_RATE);
+        assert_eq!(reader.duration(), 20 * DEFAULT_SAMPLE_RATE);
+        std::fs::remove_file(&path).unwrap();
     }
-}
 
-#[cfg(test)]
-mod test {
     #[test]
     fn hour_test() {
         let hour: u32 = 9;
@@ -303,4 +716,151 @@ mod test {
         assert_eq!(middle as u8, 0b11);
         assert_eq!(low as u8, 0b11);
     }
+
+    #[test]
+    fn minute_fields_test() {
+        // BPC sends the minute as 6 plain binary bits, 2 per fragment
+        let bpc = BPC::new();
+        for (minute, expected) in [
+            (0, [0b00, 0b00, 0b00]),
+            (15, [0b00, 0b11, 0b11]),
+            (32, [0b10, 0b00, 0b00]),
+            (45, [0b10, 0b11, 0b01]),
+            (48, [0b11, 0b00, 0b00]),
+            (59, [0b11, 0b10, 0b11]),
+        ] {
+            let frame = bpc.frame(at(2023, 8, 20, 9, minute, 0));
+            assert_eq!(frame[5..8], expected.map(Some), "minute {minute}");
+        }
+    }
+
+    #[test]
+    fn year_fields_test() {
+        // the year has 7 bits: 2 per fragment 16 - 18 and the highest one at
+        // fragment 19, whose check bit only covers fragments 11 - 18
+        let bpc = BPC::new();
+        for ((year, month, day), expected) in [
+            ((2000, 8, 20), [0b00, 0b00, 0b00, 0b01]),
+            ((2023, 1, 1), [0b01, 0b01, 0b11, 0b00]),
+            ((2063, 8, 20), [0b11, 0b11, 0b11, 0b01]),
+            ((2064, 1, 1), [0b00, 0b00, 0b00, 0b10]),
+            ((2099, 1, 1), [0b10, 0b00, 0b11, 0b11]),
+        ] {
+            let t = at(year, month, day, 9, 15, 0);
+            let frame = bpc.frame(t);
+            assert_eq!(frame[16..20], expected.map(Some), "year {year}");
+            assert_eq!(BPC::decode(&widths(&bpc, t)).unwrap(), t, "year {year}");
+        }
+    }
+
+    #[test]
+    fn year_range_test() {
+        let bpc = BPC::new();
+        assert_eq!(bpc.frame(at(1999, 12, 31, 23, 59, 40)), [None; FRAME_LEN]);
+        assert_eq!(bpc.frame(at(2100, 1, 1, 0, 0, 0)), [None; FRAME_LEN]);
+        for t in [at(2000, 1, 1, 0, 0, 0), at(2099, 12, 31, 23, 59, 40)] {
+            assert_eq!(BPC::decode(&widths(&bpc, t)).unwrap(), t);
+        }
+
+        // the 7 bits have room for 2100: 2036 with the highest bit set
+        let t = at(2036, 1, 1, 0, 0, 0);
+        let mut w = widths(&bpc, t);
+        w[19] = crate::core::width_ms(bpc.frame(t)[19].unwrap() | 0b10);
+        let err = BPC::decode(&w).unwrap_err();
+        assert!(err.to_string().contains("past 2099"), "{err}");
+    }
+
+    #[test]
+    fn minute_sync_test() {
+        let bpc = BPC::new();
+        for (second, third) in [(0, 0), (20, 1), (40, 2)] {
+            let t = at(2023, 8, 20, 9, 15, second);
+            assert_eq!(bpc.code_at(t), None);
+            let next = t + chrono::Duration::seconds(1);
+            assert_eq!(bpc.code_at(next), Some(third));
+        }
+    }
+
+    #[test]
+    fn rollover_test() {
+        let bpc = BPC::new();
+        // last frame of a day, first frame of the next
+        for (before, after) in [
+            // sunday to monday, weekday 7 back to 1
+            (at(2023, 8, 20, 23, 59, 40), at(2023, 8, 21, 0, 0, 0)),
+            (at(2023, 1, 31, 23, 59, 40), at(2023, 2, 1, 0, 0, 0)),
+            (at(2024, 2, 28, 23, 59, 40), at(2024, 2, 29, 0, 0, 0)),
+            (at(2024, 2, 29, 23, 59, 40), at(2024, 3, 1, 0, 0, 0)),
+            (at(2023, 12, 31, 23, 59, 40), at(2024, 1, 1, 0, 0, 0)),
+        ] {
+            // 23:59:59 still carries the old day, 00:00:00 the new one
+            for i in 0..40 {
+                let t = before + chrono::Duration::seconds(i);
+                let expected = if i < 20 { before } else { after };
+                assert_eq!(BPC::decode(&widths(&bpc, t)).unwrap(), expected, "{t}");
+            }
+            // day low, month low and year low of either frame
+            let code = |t: ZonedDateTime, fragment| {
+                bpc.code_at(t + chrono::Duration::seconds(fragment))
+                    .unwrap() as u32
+            };
+            for (t, fragment, expected) in [
+                (before, 13, before.day() & 0b11),
+                (after, 13, after.day() & 0b11),
+                (before, 15, before.month() & 0b11),
+                (after, 15, after.month() & 0b11),
+                (before, 18, (before.year() as u32 - 2000) & 0b11),
+                (after, 18, (after.year() as u32 - 2000) & 0b11),
+            ] {
+                assert_eq!(code(t, fragment), expected, "{t} fragment {fragment}");
+            }
+            // a leap second 23:59:60 repeats the last fragment
+            let last = before + chrono::Duration::seconds(19);
+            let leap = last.with_nanosecond(1_000_000_000).unwrap();
+            assert_eq!(bpc.code_at(leap), bpc.code_at(last));
+        }
+    }
+
+    #[test]
+    fn full_day_test() {
+        let bpc = BPC::new();
+        // noon to noon, across the following midnight
+        for (y, mo, d) in [
+            (2000, 1, 1),
+            (2023, 8, 20),
+            (2023, 12, 31),
+            (2024, 2, 28),
+            (2024, 2, 29),
+            (2063, 12, 31),
+            // the day after would end in 2100
+            (2099, 12, 30),
+        ] {
+            let start = at(y, mo, d, 12, 0, 0);
+            for i in 0..24 * 3600 {
+                let t = start + chrono::Duration::seconds(i);
+                match bpc.code_at(t) {
+                    None => assert_eq!(t.second() % 20, 0, "{t}"),
+                    Some(code) => assert!(code <= 3, "{t}"),
+                }
+            }
+        }
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::test_runner::Config::with_cases(5000))]
+
+        #[test]
+        fn random_round_trip_test(
+            elapsed in 0..=(at(2099, 12, 31, 23, 59, 59) - at(2000, 1, 1, 0, 0, 0)).num_seconds()
+        ) {
+            let bpc = BPC::new();
+            let t = at(2000, 1, 1, 0, 0, 0) + chrono::Duration::seconds(elapsed);
+            proptest::prop_assert_eq!(
+                BPC::decode(&widths(&bpc, t)).unwrap(),
+                bpc.frame_start(t),
+                "{}",
+                t
+            );
+        }
+    }
 }
@@ -0,0 +1,89 @@
+use crate::{cst, ZonedDateTime};
+use chrono::Duration;
+use std::sync::{Arc, Mutex};
+
+/// Source of the time transmitted by [`SignalSamples`](crate::SignalSamples).
+pub trait Clock {
+    fn now(&self) -> ZonedDateTime;
+}
+
+/// The system wall clock, in China Standard Time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> ZonedDateTime {
+        cst()
+    }
+}
+
+/// The system clock shifted to start from a chosen time, then advancing in
+/// real time from there, in China Standard Time.
+#[derive(Clone, Copy, Debug)]
+pub struct SimulatedClock {
+    offset: Duration,
+}
+
+impl SimulatedClock {
+    /// Creates a clock that reads `t` now.
+    pub fn starting_at(t: ZonedDateTime) -> Self {
+        Self {
+            offset: t.signed_duration_since(cst()),
+        }
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> ZonedDateTime {
+        cst() + self.offset
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// Clones share the same time, so a test can keep one handle and hand the
+/// other to the wave.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<ZonedDateTime>>,
+}
+
+impl MockClock {
+    pub fn new(t: ZonedDateTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(t)),
+        }
+    }
+
+    pub fn set(&self, t: ZonedDateTime) {
+        *self.now.lock().unwrap() = t;
+    }
+
+    pub fn advance(&self, d: Duration) {
+        *self.now.lock().unwrap() += d;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> ZonedDateTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+
+    #[test]
+    fn simulated_clock_test() {
+        let t = FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2023, 12, 31, 15, 59, 58)
+            .unwrap();
+        let clock = SimulatedClock::starting_at(t);
+        let elapsed = clock.now() - t;
+        assert!(elapsed >= Duration::zero() && elapsed < Duration::seconds(1));
+        assert_eq!(clock.now().offset().local_minus_utc(), 8 * 3600);
+    }
+}
@@ -0,0 +1,230 @@
+//! Settings read from the TOML file given to `--config`.
+//!
+//! Every key is named after the flag it stands for, and a flag on the command
+//! line still overrides the file:
+//!
+//! ```toml
+//! protocol = "bpc"
+//! device = "USB Audio"
+//! carrier-divisor = 4
+//! sample-rate = 48000
+//! gain = 0.8
+//! offset = 30
+//! ```
+
+use crate::{idle, protocol, waveform, Args, Protocol};
+use anyhow::{Context, Result};
+use bpcsync::{Idle, Waveform};
+use serde::{de::Error, Deserialize, Deserializer};
+use std::path::Path;
+
+/// The settings of a config file; each one left unset keeps the default of
+/// its flag.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// `protocol`: bpc, wwvb, dcf77 or jjy; bpc by default.
+    #[serde(deserialize_with = "protocol_name")]
+    pub protocol: Option<Protocol>,
+    /// `carrier-khz`: jjy station frequency, 40 or 60; 40 by default.
+    pub carrier_khz: Option<u32>,
+    /// `carrier-divisor`: subharmonic of the station frequency to play; the
+    /// default tone of the protocol by default.
+    #[serde(deserialize_with = "positive")]
+    pub carrier_divisor: Option<u32>,
+    /// `sample-rate` in Hz; the native rate of the device by default.
+    #[serde(deserialize_with = "positive")]
+    pub sample_rate: Option<u32>,
+    /// `device`: output device index or name substring; the default device
+    /// by default.
+    pub device: Option<String>,
+    /// `backend`: audio backend, e.g. alsa or jack; the default one of the
+    /// system by default.
+    pub backend: Option<String>,
+    /// `gain`: 1 by default.
+    pub gain: Option<f32>,
+    /// `normalize`: scale a gain that would clip down to full scale; false
+    /// by default.
+    pub normalize: Option<bool>,
+    /// `modulation-depth`: share of the carrier removed during a reduction,
+    /// 1 by default.
+    #[serde(rename = "modulation-depth")]
+    pub depth: Option<f32>,
+    /// `waveform`: sine or square; sine by default.
+    #[serde(deserialize_with = "waveform_name")]
+    pub waveform: Option<Waveform>,
+    /// `invert`: full carrier for the symbol width; false by default.
+    pub invert: Option<bool>,
+    /// `idle`: silence, full or reduced:DEPTH during the frame marker; full
+    /// by default.
+    #[serde(deserialize_with = "idle_name")]
+    pub idle: Option<Idle>,
+    /// `ramp-ms`: length of the envelope ramps; 2 by default.
+    pub ramp_ms: Option<u64>,
+    /// `offset`: output latency to make up for, in ms; 0 by default.
+    #[serde(rename = "offset")]
+    pub offset_ms: Option<u64>,
+    /// `channels`: 1 by default.
+    pub channels: Option<u16>,
+    /// `channel-phase`: carrier phase of the channels after the first, in
+    /// degrees; 0 by default.
+    pub channel_phase: Option<f32>,
+    /// `reopen-attempts`: times to try reopening a device that stopped
+    /// pulling samples; 3 by default.
+    pub reopen_attempts: Option<u32>,
+    /// `reopen-backoff-ms`: wait before the first attempt, doubled after each
+    /// failed one up to a minute; 1000 by default.
+    pub reopen_backoff_ms: Option<u64>,
+    /// `aligned`: start at the top of the next minute; false by default.
+    pub aligned: Option<bool>,
+    /// `precise-timing`: busy-wait the end of each second; false by default.
+    pub precise_timing: Option<bool>,
+    /// `wake-margin-us`: how long past each second the update thread wakes
+    /// up; 500 by default.
+    pub wake_margin_us: Option<u64>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("unable to read config {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("invalid config {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml_edit::de::from_str(text)?)
+    }
+
+    // sets every setting of the file, before the flags are parsed
+    pub fn apply(self, args: &mut Args) {
+        if let Some(v) = self.protocol {
+            args.protocol = v;
+        }
+        args.carrier_khz = self.carrier_khz.or(args.carrier_khz);
+        args.carrier_divisor = self.carrier_divisor.or(args.carrier_divisor);
+        args.sample_rate = self.sample_rate.or(args.sample_rate);
+        args.device = self.device.or(args.device.take());
+        args.backend = self.backend.or(args.backend.take());
+        args.gain = self.gain.unwrap_or(args.gain);
+        args.normalize = self.normalize.unwrap_or(args.normalize);
+        args.depth = self.depth.unwrap_or(args.depth);
+        args.waveform = self.waveform.unwrap_or(args.waveform);
+        args.invert = self.invert.unwrap_or(args.invert);
+        args.idle = self.idle.unwrap_or(args.idle);
+        args.ramp_ms = self.ramp_ms.or(args.ramp_ms);
+        args.offset_ms = self.offset_ms.unwrap_or(args.offset_ms);
+        args.channels = self.channels.unwrap_or(args.channels);
+        args.channel_phase = self.channel_phase.unwrap_or(args.channel_phase);
+        args.reopen_attempts = self.reopen_attempts.unwrap_or(args.reopen_attempts);
+        args.reopen_backoff_ms = self.reopen_backoff_ms.unwrap_or(args.reopen_backoff_ms);
+        args.aligned = self.aligned.unwrap_or(args.aligned);
+        args.precise_timing = self.precise_timing.unwrap_or(args.precise_timing);
+        args.wake_margin_us = self.wake_margin_us.unwrap_or(args.wake_margin_us);
+    }
+}
+
+// the names the flags take, e.g. `idle = "reduced:0.5"`
+fn named<'de, D: Deserializer<'de>, T>(
+    d: D,
+    parse: fn(&str) -> Result<T>,
+) -> Result<Option<T>, D::Error> {
+    let name = String::deserialize(d)?;
+    parse(&name)
+        .map(Some)
+        .map_err(|e| D::Error::custom(format!("{e:#}")))
+}
+
+fn protocol_name<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Protocol>, D::Error> {
+    named(d, protocol)
+}
+
+fn waveform_name<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Waveform>, D::Error> {
+    named(d, waveform)
+}
+
+fn idle_name<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Idle>, D::Error> {
+    named(d, idle)
+}
+
+fn positive<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+    match u32::deserialize(d)? {
+        0 => Err(D::Error::custom("must be positive")),
+        v => Ok(Some(v)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let config = Config::parse(
+            r#"
+            protocol = "wwvb"
+            device = "usb"
+            carrier-divisor = 4
+            gain = 1
+            modulation-depth = 0.5
+            waveform = "square"
+            idle = "reduced:0.5"
+            reopen-attempts = 10
+            aligned = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                protocol: Some(Protocol::Wwvb),
+                device: Some("usb".to_string()),
+                carrier_divisor: Some(4),
+                gain: Some(1.),
+                depth: Some(0.5),
+                waveform: Some(Waveform::Square),
+                idle: Some(Idle::ReducedCarrier(0.5)),
+                reopen_attempts: Some(10),
+                aligned: Some(true),
+                ..Config::default()
+            }
+        );
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+
+        assert!(Config::parse("volume = 3").is_err());
+        assert!(Config::parse("gain = \"loud\"").is_err());
+        assert!(Config::parse("channels = 70000").is_err());
+        assert!(Config::parse("sample-rate = 0").is_err());
+        assert!(Config::parse("carrier-divisor = 0").is_err());
+        assert!(Config::parse("idle = \"half\"").is_err());
+        assert!(Config::parse("[output]\ndevice = \"usb\"").is_err());
+    }
+
+    #[test]
+    fn override_test() {
+        let path = std::env::temp_dir().join("bpcsync_override_test.toml");
+        std::fs::write(&path, "gain = 0.5\nchannels = 2\noffset = 30\n").unwrap();
+        let cli = |flags: &[&str]| {
+            let config = ["--config", path.to_str().unwrap()];
+            Args::parse_from(config.iter().chain(flags).map(|f| f.to_string())).unwrap()
+        };
+
+        // the file sets what the flags leave alone
+        let args = cli(&[]);
+        assert_eq!((args.gain, args.channels, args.offset_ms), (0.5, 2, 30));
+        assert_eq!(args.depth, 1.);
+
+        // and the flags win over the file
+        let args = cli(&["--gain", "0.25", "--modulation-depth", "0.5"]);
+        assert_eq!((args.gain, args.channels, args.depth), (0.25, 2, 0.5));
+
+        // wherever `--config` is
+        let args = Args::parse_from(
+            ["--channels", "1", "--config", path.to_str().unwrap()].map(String::from),
+        )
+        .unwrap();
+        assert_eq!((args.gain, args.channels), (0.5, 1));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(Args::parse_from(["--config".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! The BPC encoder on plain integers, without std, alloc or chrono, so it
+//! can also drive a real 68.5 kHz carrier from a microcontroller. Build with
+//! `default-features = false` to get only this module.
+
+/// Frequency in Hz of the BPC carrier broadcast from Shangqiu.
+pub const BPC_FREQ: u32 = 68500;
+
+/// Length in seconds of a BPC frame, one fragment per second.
+pub const FRAME_LEN: usize = 20;
+
+/// Width in ms of the power reduction carrying each 2-bit code, shared by
+/// the encoder and the decoders.
+pub const CODE_WIDTHS: [(u8, u32); 4] = [(0b00, 100), (0b01, 200), (0b10, 300), (0b11, 400)];
+
+/// Code of fragment 2, reserved. The format of BPC has no public spec, and
+/// the decodes of the broadcast it is known from all read `00` there.
+/// Receivers ignore it, and so does `BPC::decode`.
+pub const RESERVED: u8 = 0b00;
+
+/// Years a frame carries. The year goes out as its last two digits, so
+/// although fragments 16 - 19 have room for 7 bits, receivers only read
+/// 2000 - 2099 back.
+pub const YEARS: core::ops::RangeInclusive<u16> = 2000..=2099;
+
+/// The broken-down China Standard Time a BPC second is encoded from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fields {
+    /// Full year, within [`YEARS`].
+    pub year: u16,
+    /// 1 - 12.
+    pub month: u8,
+    /// 1 - 31.
+    pub day: u8,
+    /// 1 (Monday) - 7 (Sunday).
+    pub weekday: u8,
+    /// 12-hour clock hour, 0 - 11; 12 AM and 12 PM are both 0.
+    pub hour: u8,
+    pub pm: bool,
+    /// 0 - 59.
+    pub minute: u8,
+    /// 0 - 59.
+    pub second: u8,
+}
+
+/// Returns the 2-bit code transmitted during the second of `fields`, `None`
+/// for the frame marker at fragment 0.
+///
+/// Also `None` for a year outside [`YEARS`], rather than bits a receiver
+/// would read as another year. Every second then goes out as a marker, which
+/// no receiver locks onto. The same goes for any other field out of its
+/// documented range, such as a second above 59.
+pub fn code(fields: &Fields) -> Option<u8> {
+    let valid = YEARS.contains(&fields.year)
+        && (1..=12).contains(&fields.month)
+        && (1..=31).contains(&fields.day)
+        && (1..=7).contains(&fields.weekday)
+        && fields.hour <= 11
+        && fields.minute <= 59
+        && fields.second <= 59;
+    if !valid {
+        return None;
+    }
+    let year = (fields.year - 2000) as u32;
+    let month = fields.month as u32;
+    let day = fields.day as u32;
+    let weekday = fields.weekday as u32;
+    let hour = fields.hour as u32;
+    let minute = fields.minute as u32;
+    let second = fields.second as u32;
+
+    let fragment = second % FRAME_LEN as u32;
+    match fragment {
+        0 => {
+            // empty
+            None
+        }
+        1 => {
+            // seconds, 01 / 21 / 41
+            let v: u32 = match second {
+                1 => 0,
+                21 => 1,
+                41 => 2,
+                _ => return None,
+            };
+            Some(v as u8)
+        }
+        2 => {
+            // reserved
+            Some(RESERVED)
+        }
+        3 => {
+            // hour high
+            let high = hour >> 2;
+            Some(high as u8)
+        }
+        4 => {
+            // hour low
+            let low = hour & 0b11;
+            Some(low as u8)
+        }
+        5 => {
+            // minute high
+            let high = minute >> 4;
+            Some(high as u8)
+        }
+        6 => {
+            // minute middle
+            let middle = (minute >> 2) & 0b11;
+            Some(middle as u8)
+        }
+        7 => {
+            // minute low
+            let low = minute & 0b11;
+            Some(low as u8)
+        }
+        8 => {
+            // weekday high
+            let high = weekday >> 2;
+            Some(high as u8)
+        }
+        9 => {
+            // weekday low
+            let low = weekday & 0b11;
+            Some(low as u8)
+        }
+        10 => {
+            // check & am/pm
+            let mut v: u8 = if fields.pm { 0b10 } else { 0b00 };
+            // second range: 0 - 59
+            let s = match second {
+                1..=20 => 0b00,
+                21..=40 => 0b01,
+                _ => 0b11,
+            };
+            v |= time_check(s, hour, minute, weekday);
+            Some(v)
+        }
+        11 => {
+            // day high
+            let high = day >> 4;
+            Some(high as u8)
+        }
+        12 => {
+            // day middle
+            let middle = (day >> 2) & 0b11;
+            Some(middle as u8)
+        }
+        13 => {
+            // day low
+            let low = day & 0b11;
+            Some(low as u8)
+        }
+        14 => {
+            // month high
+            let high = month >> 2;
+            Some(high as u8)
+        }
+        15 => {
+            // month low
+            let low = month & 0b11;
+            Some(low as u8)
+        }
+        16 => {
+            // year high
+            let high = (year >> 4) & 0b11;
+            Some(high as u8)
+        }
+        17 => {
+            // year middle
+            let middle = (year >> 2) & 0b11;
+            Some(middle as u8)
+        }
+        18 => {
+            // year low
+            let low = year & 0b11;
+            Some(low as u8)
+        }
+        19 => {
+            // check & year highest bit
+            let year_highest = (year >> 6) & 0b1;
+            let mut v = (year_highest << 1) as u8;
+            v |= date_check(day, month, year);
+            Some(v)
+        }
+        _ => None,
+    }
+}
+
+/// Width in ms of the power reduction carrying `code`, `None` for a code
+/// above `0b11`.
+pub fn width_ms(code: u8) -> Option<u32> {
+    CODE_WIDTHS.get(code as usize).map(|&(_, width)| width)
+}
+
+// check bit of fragment 10. The first value is summed as it is and the others
+// by their set bits, as this crate always has: nothing captured off the air
+// confirms or refutes it yet
+pub(crate) fn time_check(s: u32, hour: u32, minute: u32, weekday: u32) -> u8 {
+    check(s, &[hour, minute, weekday])
+}
+
+// check bit of fragment 19, summed the same way
+pub(crate) fn date_check(day: u32, month: u32, year: u32) -> u8 {
+    check(day, &[month, year & 0b111111])
+}
+
+fn check(first: u32, rest: &[u32]) -> u8 {
+    let c = rest.iter().fold(first, |acc, v| acc + v.count_ones());
+    (c % 2) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reserved_test() {
+        let mut fields = Fields {
+            year: 2023,
+            month: 8,
+            day: 20,
+            weekday: 7,
+            hour: 9,
+            pm: false,
+            minute: 15,
+            second: 2,
+        };
+        // the same in every frame of the minute, whatever the time
+        for (second, hour, pm) in [(2, 9, false), (22, 0, true), (42, 11, true)] {
+            fields.second = second;
+            fields.hour = hour;
+            fields.pm = pm;
+            assert_eq!(code(&fields), Some(RESERVED), "second {second}");
+        }
+        assert_eq!(RESERVED, 0b00);
+    }
+
+    #[test]
+    fn code_test() {
+        // 2023-08-20 09:15:00, a sunday
+        let mut fields = Fields {
+            year: 2023,
+            month: 8,
+            day: 20,
+            weekday: 7,
+            hour: 9,
+            pm: false,
+            minute: 15,
+            second: 0,
+        };
+        let expected = [0, 0, 2, 1, 0, 3, 3, 1, 3, 1, 1, 1, 0, 2, 0, 1, 1, 3, 1];
+        assert_eq!(code(&fields), None);
+        for (i, expected) in expected.into_iter().enumerate() {
+            fields.second = i as u8 + 1;
+            assert_eq!(code(&fields), Some(expected), "fragment {}", i + 1);
+        }
+        assert_eq!(width_ms(0b11), Some(400));
+        assert_eq!(width_ms(0b100), None);
+
+        // the years either side of the range carry nothing
+        for (year, expected) in [
+            (1999, None),
+            (2000, Some(0b00)),
+            (2099, Some(0b11)),
+            (2100, None),
+        ] {
+            fields.year = year;
+            fields.second = 18;
+            assert_eq!(code(&fields), expected, "year {year}");
+        }
+        for (i, (code, _)) in CODE_WIDTHS.into_iter().enumerate() {
+            assert_eq!(code as usize, i);
+        }
+    }
+
+    #[test]
+    fn out_of_range_test() {
+        let fields = Fields {
+            year: 2023,
+            month: 8,
+            day: 20,
+            weekday: 7,
+            hour: 9,
+            pm: false,
+            minute: 15,
+            second: 3,
+        };
+        assert_eq!(code(&fields), Some(0b10));
+        let invalid = [
+            Fields {
+                second: 60,
+                ..fields
+            },
+            Fields {
+                second: 255,
+                ..fields
+            },
+            Fields {
+                minute: 60,
+                ..fields
+            },
+            Fields { hour: 12, ..fields },
+            Fields { hour: 16, ..fields },
+            Fields {
+                weekday: 0,
+                ..fields
+            },
+            Fields {
+                weekday: 8,
+                ..fields
+            },
+            Fields { day: 0, ..fields },
+            Fields { day: 32, ..fields },
+            Fields { month: 0, ..fields },
+            Fields {
+                month: 13,
+                ..fields
+            },
+        ];
+        for fields in invalid {
+            assert_eq!(code(&fields), None, "{fields:?}");
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use crate::{TimeSignal, ZonedDateTime};
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, TimeZone, Timelike, Utc};
+
+const DCF77_FREQ: u32 = 77500;
+// normally speakers only produce sound frequency under 20khz
+const DEFAULT_CARRIER: f32 = (DCF77_FREQ / 5) as f32;
+
+// the fields a frame carries in bcd, least significant bit first
+#[derive(Clone, Copy, Debug)]
+enum Field {
+    Minute,
+    Hour,
+    Day,
+    Weekday,
+    Month,
+    Year,
+}
+
+// the bcd field and bit carried by each data second of the frame
+fn position(second: u32) -> Option<(Field, u32)> {
+    match second {
+        21..=27 => Some((Field::Minute, second - 21)),
+        29..=34 => Some((Field::Hour, second - 29)),
+        36..=41 => Some((Field::Day, second - 36)),
+        42..=44 => Some((Field::Weekday, second - 42)),
+        45..=49 => Some((Field::Month, second - 45)),
+        50..=57 => Some((Field::Year, second - 50)),
+        _ => None,
+    }
+}
+
+fn bcd(v: u32) -> u32 {
+    ((v / 10) << 4) | (v % 10)
+}
+
+fn from_bcd(v: u32) -> u32 {
+    (v >> 4) * 10 + (v & 0xf)
+}
+
+fn parity(values: &[u32]) -> bool {
+    let c: u32 = values.iter().map(|v| v.count_ones()).sum();
+    c % 2 == 1
+}
+
+// central european summer time applies from 01:00 UTC of the last sunday of
+// march to 01:00 UTC of the last sunday of october
+fn is_summer_time(t: DateTime<Utc>) -> bool {
+    let switch = |month| {
+        let last = NaiveDate::from_ymd_opt(t.year(), month, 31).unwrap();
+        let sunday = last - chrono::Duration::days(last.weekday().num_days_from_sunday() as i64);
+        sunday.and_hms_opt(1, 0, 0).unwrap().and_utc()
+    };
+    switch(3) <= t && t < switch(10)
+}
+
+fn offset(summer_time: bool) -> FixedOffset {
+    let hours = if summer_time { 2 } else { 1 };
+    FixedOffset::east_opt(hours * 3600).unwrap()
+}
+
+/// The DCF77 time code broadcast on 77.5 kHz from Mainflingen, Germany.
+///
+/// A frame lasts a minute and announces the German civil time (CET or CEST)
+/// of the minute mark that ends it. Seconds 0 - 58 carry one bit each, sent
+/// as a 100 ms (0) or 200 ms (1) reduction; second 59 is left unmodulated to
+/// mark the minute. Warning, call and announcement bits are left clear.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Default)]
+pub struct DCF77 {}
+
+impl DCF77 {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Returns the bit transmitted during the second of `t`, or `None` for
+    /// the minute mark at second 59.
+    pub fn bit_at(&self, t: ZonedDateTime) -> Option<bool> {
+        let second = t.second();
+        // the frame announces the following minute
+        let next = (self.frame_start(t) + chrono::Duration::minutes(1)).with_timezone(&Utc);
+        let summer_time = is_summer_time(next);
+        let local = next.with_timezone(&offset(summer_time));
+        let field = |field| match field {
+            Field::Minute => bcd(local.minute()),
+            Field::Hour => bcd(local.hour()),
+            Field::Day => bcd(local.day()),
+            Field::Weekday => local.weekday().number_from_monday(),
+            Field::Month => bcd(local.month()),
+            Field::Year => bcd(local.year() as u32 % 100),
+        };
+
+        let bit = match second {
+            17 => summer_time,
+            18 => !summer_time,
+            // start of encoded time
+            20 => true,
+            28 => parity(&[field(Field::Minute)]),
+            35 => parity(&[field(Field::Hour)]),
+            58 => parity(&[
+                field(Field::Day),
+                field(Field::Weekday),
+                field(Field::Month),
+                field(Field::Year),
+            ]),
+            59 => return None,
+            s => match position(s) {
+                Some((f, bit)) => (field(f) >> bit) & 1 == 1,
+                None => false,
+            },
+        };
+        Some(bit)
+    }
+
+    /// Returns the bits of the whole minute containing `t`.
+    pub fn frame(&self, t: ZonedDateTime) -> [Option<bool>; 60] {
+        let start = self.frame_start(t);
+        std::array::from_fn(|i| self.bit_at(start + chrono::Duration::seconds(i as i64)))
+    }
+
+    /// Recovers the time from the pulse widths of one frame, the inverse of
+    /// [`DCF77::frame`].
+    ///
+    /// `widths[59]` must be the minute mark (`None`) and every other entry a
+    /// 100 or 200 ms width. All three parity bits are verified. The returned
+    /// time is the announced minute, in CET or CEST as flagged by the frame.
+    pub fn decode(widths: &[Option<u32>; 60]) -> Result<ZonedDateTime> {
+        ensure!(widths[59].is_none(), "second 59 is not a minute mark");
+        let mut bits = [false; 59];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = match widths[i] {
+                Some(100) => false,
+                Some(200) => true,
+                Some(w) => bail!("unexpected width {w}ms at second {i}"),
+                None => bail!("missing pulse at second {i}"),
+            };
+        }
+        ensure!(!bits[0], "start of minute bit is set");
+        ensure!(bits[20], "start of time bit is clear");
+        ensure!(bits[17] != bits[18], "invalid time zone bits");
+
+        let mut fields = [0u32; 6];
+        for (i, &bit) in bits.iter().enumerate() {
+            if let (true, Some((field, n))) = (bit, position(i as u32)) {
+                fields[field as usize] |= 1 << n;
+            }
+        }
+        ensure!(
+            parity(&fields[..1]) == bits[28],
+            "minute parity bit mismatch"
+        );
+        ensure!(
+            parity(&fields[1..2]) == bits[35],
+            "hour parity bit mismatch"
+        );
+        ensure!(parity(&fields[2..]) == bits[58], "date parity bit mismatch");
+
+        let weekday = fields[Field::Weekday as usize];
+        let [minute, hour, day, _, month, year] = fields.map(from_bcd);
+        let t = offset(bits[17])
+            .with_ymd_and_hms(2000 + year as i32, month, day, hour, minute, 0)
+            .single()
+            .with_context(|| {
+                format!("invalid time 20{year:02}-{month:02}-{day:02} {hour:02}:{minute:02}")
+            })?;
+        ensure!(
+            t.weekday().number_from_monday() == weekday,
+            "weekday {weekday} does not match {}",
+            t.date_naive()
+        );
+        Ok(t)
+    }
+}
+
+impl TimeSignal for DCF77 {
+    fn symbol_width(&self, t: ZonedDateTime) -> Option<u32> {
+        self.bit_at(t).map(|bit| if bit { 200 } else { 100 })
+    }
+
+    fn carrier_hz(&self) -> f32 {
+        DEFAULT_CARRIER
+    }
+
+    fn frame_len_secs(&self) -> u32 {
+        60
+    }
+
+    /// Describes what is transmitted during the second of `t`, e.g.
+    /// `09:14:21 [21] minute = 1 (200ms)`.
+    fn describe(&self, t: ZonedDateTime) -> String {
+        let second = t.second();
+        let name = match (second, position(second)) {
+            (0, _) => "start of minute",
+            (1..=14, _) => "civil warning",
+            (15, _) => "call bit",
+            (16, _) => "dst announcement",
+            (17, _) => "cest",
+            (18, _) => "cet",
+            (19, _) => "leap second",
+            (20, _) => "start of time",
+            (28, _) => "minute parity",
+            (35, _) => "hour parity",
+            (58, _) => "date parity",
+            (59, _) => "minute mark",
+            (_, Some((Field::Minute, _))) => "minute",
+            (_, Some((Field::Hour, _))) => "hour",
+            (_, Some((Field::Day, _))) => "day",
+            (_, Some((Field::Weekday, _))) => "weekday",
+            (_, Some((Field::Month, _))) => "month",
+            (_, Some((Field::Year, _))) => "year",
+            _ => unreachable!("unreachable second"),
+        };
+        let utc = t.with_timezone(&Utc);
+        let time = utc
+            .with_timezone(&offset(is_summer_time(utc)))
+            .format("%H:%M:%S");
+        match (self.bit_at(t), self.symbol_width(t)) {
+            (Some(bit), Some(width)) => {
+                format!("{time} [{second:>2}] {name} = {} ({width}ms)", bit as u8)
+            }
+            _ => format!("{time} [{second:>2}] {name}, no pulse"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> ZonedDateTime {
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(y, mo, d, h, mi, s)
+            .unwrap()
+    }
+
+    fn bits(s: &str) -> Vec<Option<bool>> {
+        s.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                '0' => Some(false),
+                '1' => Some(true),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reference_frame_test() {
+        let dcf77 = DCF77::new();
+        // announces 2023-08-20 09:15 CEST, a sunday
+        let frame = dcf77.frame(at(2023, 8, 20, 15, 14, 30));
+        let expected = "0000000000 0000000100 1101010011 0010000000 0111100010 110001000-";
+        assert_eq!(frame.to_vec(), bits(expected));
+
+        // announces 2024-02-29 15:42 CET, a thursday
+        let frame = dcf77.frame(at(2024, 2, 29, 22, 41, 0));
+        let expected = "0000000000 0000000010 1010000101 0101011001 0100101000 001001001-";
+        assert_eq!(frame.to_vec(), bits(expected));
+    }
+
+    #[test]
+    fn summer_time_test() {
+        let utc = |mo, d, h, mi| Utc.with_ymd_and_hms(2023, mo, d, h, mi, 0).unwrap();
+        assert!(!is_summer_time(utc(3, 26, 0, 59)));
+        assert!(is_summer_time(utc(3, 26, 1, 0)));
+        assert!(is_summer_time(utc(10, 29, 0, 59)));
+        assert!(!is_summer_time(utc(10, 29, 1, 0)));
+    }
+
+    fn widths(dcf77: &DCF77, t: ZonedDateTime) -> [Option<u32>; 60] {
+        let start = dcf77.frame_start(t);
+        std::array::from_fn(|i| dcf77.symbol_width(start + chrono::Duration::seconds(i as i64)))
+    }
+
+    #[test]
+    fn decode_test() {
+        let dcf77 = DCF77::new();
+        for t in [
+            at(2023, 8, 20, 15, 14, 30),
+            at(2023, 12, 31, 23, 59, 59),
+            at(2024, 2, 29, 22, 41, 0),
+            // the frame before the switch to summer time
+            at(2023, 3, 26, 8, 59, 0),
+            at(2099, 1, 1, 13, 1, 20),
+        ] {
+            let next = dcf77.frame_start(t) + chrono::Duration::minutes(1);
+            assert_eq!(DCF77::decode(&widths(&dcf77, t)).unwrap(), next);
+        }
+        let t = DCF77::decode(&widths(&dcf77, at(2023, 3, 26, 8, 59, 0))).unwrap();
+        assert_eq!(t.offset().local_minus_utc(), 2 * 3600);
+    }
+
+    #[test]
+    fn decode_error_test() {
+        let dcf77 = DCF77::new();
+        let valid = widths(&dcf77, at(2023, 8, 20, 15, 14, 30));
+
+        let mut w = valid;
+        w[30] = Some(150);
+        assert!(DCF77::decode(&w).is_err());
+
+        // flip a single minute bit so that only the parity bit disagrees
+        let mut w = valid;
+        w[22] = w[22].map(|w| 300 - w);
+        assert!(DCF77::decode(&w).is_err());
+
+        let mut w = valid;
+        w[59] = Some(100);
+        assert!(DCF77::decode(&w).is_err());
+    }
+
+    #[test]
+    fn describe_test() {
+        let dcf77 = DCF77::new();
+        assert_eq!(
+            dcf77.describe(at(2023, 8, 20, 15, 14, 21)),
+            "09:14:21 [21] minute = 1 (200ms)"
+        );
+        assert_eq!(
+            dcf77.describe(at(2023, 8, 20, 15, 14, 59)),
+            "09:14:59 [59] minute mark, no pulse"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use rodio::{
+    cpal::{self, traits::HostTrait, Host},
+    Device, DeviceTrait,
+};
+
+// name fragments of outputs that play to nowhere, like the null sink of
+// pulseaudio or the monitor of another sink
+const DUMMY_NAMES: [&str; 3] = ["null", "dummy", "monitor"];
+
+/// Returns the names of the audio backends of this build available on this
+/// system, e.g. `ALSA`, plus `JACK` in a build with the `jack` feature.
+pub fn backends() -> Vec<&'static str> {
+    cpal::available_hosts().iter().map(|id| id.name()).collect()
+}
+
+/// Returns the audio backend `name`, case-insensitively, among
+/// [`backends`], or the default one when `name` is `None` or the backend
+/// cannot be used.
+pub fn host(name: Option<&str>) -> Host {
+    let Some(name) = name else {
+        return cpal::default_host();
+    };
+    let hosts = cpal::available_hosts();
+    let Some(id) = hosts.iter().find(|id| id.name().eq_ignore_ascii_case(name)) else {
+        warn!(
+            "audio backend `{name}` is not available, using the default one; available: {}",
+            backends().join(", ")
+        );
+        return cpal::default_host();
+    };
+    match cpal::host_from_id(*id) {
+        Ok(host) => {
+            info!("using the {} audio backend", id.name());
+            host
+        }
+        Err(e) => {
+            warn!(
+                "unable to use the {} audio backend, using the default one: {e}",
+                id.name()
+            );
+            cpal::default_host()
+        }
+    }
+}
+
+/// Returns every output device of `host`, in enumeration order.
+pub fn output_devices(host: &Host) -> Result<Vec<Device>> {
+    Ok(host.output_devices()?.collect())
+}
+
+/// Returns the default output device of `host`.
+pub fn default_output_device(host: &Host) -> Option<Device> {
+    host.default_output_device()
+}
+
+/// Returns the output device to use when none is asked for: the default one
+/// unless it looks like a dummy (see [`is_dummy`]) or cannot be configured,
+/// else the first real device that can, else the first one that can at all.
+pub fn preferred_output_device(host: &Host) -> Option<Device> {
+    let mut devices: Vec<Device> = default_output_device(host).into_iter().collect();
+    let has_default = !devices.is_empty();
+    match output_devices(host) {
+        Ok(all) => devices.extend(all),
+        Err(e) => warn!("unable to enumerate output devices: {e:#}"),
+    }
+    let names: Vec<String> = devices
+        .iter()
+        .map(|d| d.name().unwrap_or_default())
+        .collect();
+    let works = |i: usize| devices[i].default_output_config().is_ok();
+    let i = pick_device(&names, works)?;
+    match (has_default, i) {
+        (true, 0) => info!("using the default output device {}", names[0]),
+        (true, _) if is_dummy(&names[0]) => info!(
+            "the default output device {} looks like a dummy, using {} instead",
+            names[0], names[i]
+        ),
+        (true, _) => info!(
+            "the default output device {} does not work, using {} instead",
+            names[0], names[i]
+        ),
+        (false, _) => info!("no default output device, using {}", names[i]),
+    }
+    if is_dummy(&names[i]) {
+        warn!(
+            "no real output device found, {} may play to nowhere",
+            names[i]
+        );
+    }
+    Some(devices.swap_remove(i))
+}
+
+/// Whether `name` looks like an output that plays to nowhere, such as a
+/// null sink or the monitor of another one.
+pub fn is_dummy(name: &str) -> bool {
+    let name = name.to_lowercase();
+    DUMMY_NAMES.iter().any(|dummy| name.contains(dummy))
+}
+
+/// Finds an output device by the index printed by `bpcsync devices`, or by a
+/// case-insensitive substring of its name.
+pub fn find_output_device(host: &Host, query: &str) -> Result<Option<Device>> {
+    let mut devices = output_devices(host)?;
+    let names: Vec<String> = devices
+        .iter()
+        .map(|d| d.name().unwrap_or_default())
+        .collect();
+    Ok(match_device(&names, query).map(|i| devices.swap_remove(i)))
+}
+
+/// Returns the highest sample rate `device` can output at.
+pub fn max_sample_rate(device: &Device) -> Result<u32> {
+    let configs = device.supported_output_configs()?;
+    configs
+        .map(|config| config.max_sample_rate().0)
+        .max()
+        .context("the device reports no output configuration")
+}
+
+/// Returns the smallest divisor of `station_hz` whose subharmonic stays
+/// clear of the Nyquist frequency of `sample_rate`, below 0.4 of the rate.
+///
+/// `None` when no divisor up to 1000 does, e.g. for a `sample_rate` of 0 or
+/// a non-finite `station_hz`.
+pub fn lowest_divisor(station_hz: f32, sample_rate: u32) -> Option<u32> {
+    let limit = 0.4 * sample_rate as f32;
+    // a thousandth of any station is a hum no receiver picks up
+    (1..=1000).find(|&d| station_hz / (d as f32) < limit)
+}
+
+// the first of `names` that is not a dummy and `works`, else the first that
+// works at all
+fn pick_device(names: &[String], works: impl Fn(usize) -> bool) -> Option<usize> {
+    let real = (0..names.len()).find(|&i| !is_dummy(&names[i]) && works(i));
+    real.or_else(|| (0..names.len()).find(|&i| works(i)))
+}
+
+fn match_device(names: &[String], query: &str) -> Option<usize> {
+    if let Ok(index) = query.parse::<usize>() {
+        if index < names.len() {
+            return Some(index);
+        }
+    }
+    let query = query.to_lowercase();
+    names
+        .iter()
+        .position(|name| name.to_lowercase().contains(&query))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn match_device_test() {
+        let names = ["default".to_string(), "USB Audio DAC".to_string()];
+        assert_eq!(match_device(&names, "usb"), Some(1));
+        assert_eq!(match_device(&names, "DEF"), Some(0));
+        assert_eq!(match_device(&names, "1"), Some(1));
+        assert_eq!(match_device(&names, "hdmi"), None);
+    }
+
+    #[test]
+    fn pick_device_test() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let all = |_| true;
+        // the default device comes first
+        assert_eq!(pick_device(&names(&["Speakers", "USB DAC"]), all), Some(0));
+        let linux = names(&["Null Output", "Monitor of Null Output", "Built-in Audio"]);
+        assert_eq!(pick_device(&linux, all), Some(2));
+        assert_eq!(pick_device(&linux, |i| i != 2), Some(0));
+        let windows = names(&["HDMI (disconnected)", "Speakers (Realtek)"]);
+        assert_eq!(pick_device(&windows, |i| i != 0), Some(1));
+        assert_eq!(pick_device(&windows, |_| false), None);
+        assert!(is_dummy("Dummy Output") && !is_dummy("Realtek HD Audio"));
+    }
+
+    #[test]
+    fn host_test() {
+        let default = cpal::default_host().id();
+        assert_eq!(host(None).id(), default);
+        assert_eq!(host(Some("no such backend")).id(), default);
+        for name in backends() {
+            assert_eq!(host(Some(&name.to_lowercase())).id().name(), name);
+        }
+    }
+
+    #[test]
+    fn lowest_divisor_test() {
+        assert_eq!(lowest_divisor(68500., 44100), Some(4));
+        assert_eq!(lowest_divisor(68500., 22050), Some(8));
+        assert_eq!(lowest_divisor(40000., 192000), Some(1));
+        assert_eq!(lowest_divisor(68500., 0), None);
+        assert_eq!(lowest_divisor(f32::NAN, 44100), None);
+        assert_eq!(lowest_divisor(f32::INFINITY, 44100), None);
+    }
+}
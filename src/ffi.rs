@@ -0,0 +1,77 @@
+//! C functions over the BPC encoder, for callers such as Python's `ctypes`.
+//!
+//! Build them into a shared library with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib`. Each
+//! function returns 0 when it wrote its output, 1 for the frame marker, which
+//! carries nothing, and -1 for a null pointer or an unrepresentable time.
+
+use crate::{bpc::cst_offset, ZonedDateTime, BPC};
+use chrono::{TimeZone, Utc};
+
+// the second `unix_secs` after the epoch, in china standard time
+fn at(unix_secs: i64) -> Option<ZonedDateTime> {
+    let t = Utc.timestamp_opt(unix_secs, 0).single()?;
+    Some(t.with_timezone(&cst_offset()))
+}
+
+/// Writes the code transmitted during the second `unix_secs` seconds after
+/// the epoch to `out_code`, see [`BPC::code_at`].
+///
+/// # Safety
+///
+/// `out_code` must be null or valid for writing an `i8`.
+#[no_mangle]
+pub unsafe extern "C" fn bpc_code_at(unix_secs: i64, out_code: *mut i8) -> i32 {
+    let Some(t) = at(unix_secs).filter(|_| !out_code.is_null()) else {
+        return -1;
+    };
+    match BPC::new().code_at(t) {
+        Some(code) => {
+            *out_code = code as i8;
+            0
+        }
+        None => 1,
+    }
+}
+
+/// Writes the width in ms of the power reduction starting the second
+/// `unix_secs` seconds after the epoch to `out_width_ms`, see
+/// [`BPC::signal_width_ms`].
+///
+/// # Safety
+///
+/// `out_width_ms` must be null or valid for writing a `u32`.
+#[no_mangle]
+pub unsafe extern "C" fn bpc_width_at(unix_secs: i64, out_width_ms: *mut u32) -> i32 {
+    let Some(t) = at(unix_secs).filter(|_| !out_width_ms.is_null()) else {
+        return -1;
+    };
+    match BPC::new().signal_width_ms(t) {
+        Some(width) => {
+            *out_width_ms = width;
+            0
+        }
+        None => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ffi_test() {
+        // 2023-08-20 09:15:07 in china, minute low = 11
+        let t = 1692494107;
+        let mut code = -1;
+        let mut width = 0;
+        unsafe {
+            assert_eq!(bpc_code_at(t, &mut code), 0);
+            assert_eq!(bpc_width_at(t, &mut width), 0);
+            assert_eq!(bpc_code_at(t - 7, &mut code), 1);
+            assert_eq!(bpc_code_at(t, std::ptr::null_mut()), -1);
+            assert_eq!(bpc_width_at(i64::MAX, &mut width), -1);
+        }
+        assert_eq!((code, width), (0b11, 400));
+    }
+}
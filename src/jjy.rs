@@ -0,0 +1,315 @@
+use crate::{wwvb::Symbol, TimeSignal, ZonedDateTime};
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{Datelike, FixedOffset, NaiveDate, Timelike};
+
+// the fields a frame carries in bcd
+#[derive(Clone, Copy, Debug)]
+enum Field {
+    Minute,
+    Hour,
+    Day,
+    Year,
+    Weekday,
+}
+
+// the bcd field and bit carried by each data second of the frame
+fn position(second: u32) -> Option<(Field, u32)> {
+    match second {
+        1..=3 => Some((Field::Minute, 7 - second)),
+        5..=8 => Some((Field::Minute, 8 - second)),
+        12..=13 => Some((Field::Hour, 17 - second)),
+        15..=18 => Some((Field::Hour, 18 - second)),
+        22..=23 => Some((Field::Day, 31 - second)),
+        25..=28 => Some((Field::Day, 32 - second)),
+        30..=33 => Some((Field::Day, 33 - second)),
+        41..=48 => Some((Field::Year, 48 - second)),
+        50..=52 => Some((Field::Weekday, 52 - second)),
+        _ => None,
+    }
+}
+
+fn is_marker(second: u32) -> bool {
+    second == 0 || second % 10 == 9
+}
+
+fn bcd(v: u32) -> u32 {
+    ((v / 100) << 8) | ((v / 10 % 10) << 4) | (v % 10)
+}
+
+fn from_bcd(v: u32) -> u32 {
+    (v >> 8) * 100 + ((v >> 4) & 0xf) * 10 + (v & 0xf)
+}
+
+fn parity(v: u32) -> bool {
+    v.count_ones() % 2 == 1
+}
+
+fn jst() -> FixedOffset {
+    // japan standard time
+    FixedOffset::east_opt(9 * 3600).unwrap()
+}
+
+// time in ms the carrier stays at full power at the start of the second
+fn high_width(symbol: Symbol) -> u32 {
+    match symbol {
+        Symbol::Marker => 200,
+        Symbol::One => 500,
+        Symbol::Zero => 800,
+    }
+}
+
+/// The JJY time code broadcast on 40 kHz from Mt. Ootakadoya (Fukushima) and
+/// on 60 kHz from Mt. Hagane (Kyushu).
+///
+/// A frame lasts a minute and carries the Japan Standard Time of the minute
+/// it starts. Unlike BPC, each second starts at full power: 200 ms for a
+/// marker, 500 ms for a one and 800 ms for a zero, then the carrier is
+/// reduced. The call sign announced at minutes 15 and 45 is not sent.
+#[allow(clippy::upper_case_acronyms)]
+pub struct JJY {
+    carrier_khz: u32,
+}
+
+impl Default for JJY {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JJY {
+    /// Creates the 40 kHz (Fukushima) signal.
+    pub fn new() -> Self {
+        Self { carrier_khz: 40 }
+    }
+
+    /// Creates the signal of the station broadcasting on `khz`, 40 or 60.
+    pub fn with_carrier_khz(khz: u32) -> Result<Self> {
+        ensure!(
+            khz == 40 || khz == 60,
+            "JJY is broadcast on 40 or 60 kHz, not {khz} kHz"
+        );
+        Ok(Self { carrier_khz: khz })
+    }
+
+    /// Returns the symbol transmitted during the second of `t`.
+    pub fn symbol_at(&self, t: ZonedDateTime) -> Symbol {
+        let t = t.with_timezone(&jst());
+        let second = t.second();
+        if is_marker(second) {
+            return Symbol::Marker;
+        }
+        let field = |field| match field {
+            Field::Minute => bcd(t.minute()),
+            Field::Hour => bcd(t.hour()),
+            Field::Day => bcd(t.ordinal()),
+            Field::Year => bcd(t.year() as u32 % 100),
+            Field::Weekday => t.weekday().num_days_from_sunday(),
+        };
+        let bit = match (second, position(second)) {
+            (_, Some((f, bit))) => (field(f) >> bit) & 1 == 1,
+            (36, None) => parity(field(Field::Hour)),
+            (37, None) => parity(field(Field::Minute)),
+            _ => false,
+        };
+        if bit {
+            Symbol::One
+        } else {
+            Symbol::Zero
+        }
+    }
+
+    /// Returns the symbols of the whole minute containing `t`.
+    pub fn frame(&self, t: ZonedDateTime) -> [Symbol; 60] {
+        let start = self.frame_start(t);
+        std::array::from_fn(|i| self.symbol_at(start + chrono::Duration::seconds(i as i64)))
+    }
+
+    /// Recovers the time from the full power widths of one frame, the inverse
+    /// of [`JJY::frame`]. Both parity bits are verified. The returned time is
+    /// the start of the minute, in Japan Standard Time.
+    pub fn decode(widths: &[Option<u32>; 60]) -> Result<ZonedDateTime> {
+        let mut fields = [0u32; 5];
+        let mut parities = [false; 2];
+        for (i, width) in widths.iter().enumerate() {
+            let second = i as u32;
+            let symbol = match width {
+                Some(200) => Symbol::Marker,
+                Some(500) => Symbol::One,
+                Some(800) => Symbol::Zero,
+                _ => bail!("unexpected width {width:?} at second {i}"),
+            };
+            ensure!(
+                (symbol == Symbol::Marker) == is_marker(second),
+                "misplaced marker at second {i}"
+            );
+            if symbol != Symbol::One {
+                continue;
+            }
+            match position(second) {
+                Some((field, bit)) => fields[field as usize] |= 1 << bit,
+                None if second == 36 || second == 37 => parities[i - 36] = true,
+                None => {}
+            }
+        }
+        ensure!(
+            parity(fields[Field::Hour as usize]) == parities[0],
+            "hour parity bit mismatch"
+        );
+        ensure!(
+            parity(fields[Field::Minute as usize]) == parities[1],
+            "minute parity bit mismatch"
+        );
+
+        let weekday = fields[Field::Weekday as usize];
+        let [minute, hour, day, year, _] = fields.map(from_bcd);
+        let year = 2000 + year as i32;
+        let t = NaiveDate::from_yo_opt(year, day)
+            .with_context(|| format!("invalid day {day} of {year}"))?
+            .and_hms_opt(hour, minute, 0)
+            .with_context(|| format!("invalid time {hour:02}:{minute:02}"))?
+            .and_local_timezone(jst())
+            .unwrap();
+        ensure!(
+            t.weekday().num_days_from_sunday() == weekday,
+            "weekday {weekday} does not match {}",
+            t.date_naive()
+        );
+        Ok(t)
+    }
+}
+
+impl TimeSignal for JJY {
+    fn symbol_width(&self, t: ZonedDateTime) -> Option<u32> {
+        Some(high_width(self.symbol_at(t)))
+    }
+
+    /// The 3rd subharmonic of the station carrier.
+    fn carrier_hz(&self) -> f32 {
+        (self.carrier_khz * 1000) as f32 / 3.
+    }
+
+    fn frame_len_secs(&self) -> u32 {
+        60
+    }
+
+    /// Describes what is transmitted during the second of `t`, e.g.
+    /// `10:15:03 [ 3] minute = One (500ms)`.
+    fn describe(&self, t: ZonedDateTime) -> String {
+        let second = t.second();
+        let name = match (second, position(second)) {
+            (s, _) if is_marker(s) => "marker",
+            (_, Some((Field::Minute, _))) => "minute",
+            (_, Some((Field::Hour, _))) => "hour",
+            (_, Some((Field::Day, _))) => "day of year",
+            (_, Some((Field::Year, _))) => "year",
+            (_, Some((Field::Weekday, _))) => "weekday",
+            (36, None) => "hour parity",
+            (37, None) => "minute parity",
+            (38 | 40, None) => "spare",
+            (53..=54, None) => "leap second",
+            _ => "unused",
+        };
+        let time = t.with_timezone(&jst()).format("%H:%M:%S");
+        let symbol = self.symbol_at(t);
+        format!(
+            "{time} [{second:>2}] {name} = {symbol:?} ({}ms)",
+            high_width(symbol)
+        )
+    }
+
+    fn inverted(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> ZonedDateTime {
+        jst().with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()
+    }
+
+    fn symbols(s: &str) -> Vec<Symbol> {
+        s.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                '0' => Symbol::Zero,
+                '1' => Symbol::One,
+                _ => Symbol::Marker,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reference_frame_test() {
+        let jjy = JJY::new();
+        // day 232, a sunday
+        let frame = jjy.frame(at(2023, 8, 20, 10, 15, 0));
+        let expected = "M00100101M 000100000M 001000011M 001000110M 000100011M 000000000M";
+        assert_eq!(frame.to_vec(), symbols(expected));
+
+        // day 60, a thursday
+        let frame = jjy.frame(at(2024, 2, 29, 15, 42, 30));
+        let expected = "M10000010M 000100101M 000000110M 000000100M 000100100M 100000000M";
+        assert_eq!(frame.to_vec(), symbols(expected));
+    }
+
+    #[test]
+    fn carrier_test() {
+        assert_eq!(JJY::new().carrier_hz(), 40000. / 3.);
+        assert_eq!(JJY::with_carrier_khz(60).unwrap().carrier_hz(), 20000.);
+        assert!(JJY::with_carrier_khz(77).is_err());
+    }
+
+    fn widths(jjy: &JJY, t: ZonedDateTime) -> [Option<u32>; 60] {
+        jjy.frame(t).map(|s| Some(high_width(s)))
+    }
+
+    #[test]
+    fn decode_test() {
+        let jjy = JJY::new();
+        for t in [
+            at(2023, 8, 20, 10, 15, 0),
+            at(2023, 12, 31, 23, 59, 59),
+            at(2024, 2, 29, 15, 42, 30),
+            at(2000, 1, 1, 0, 0, 0),
+        ] {
+            let start = jjy.frame_start(t);
+            assert_eq!(JJY::decode(&widths(&jjy, t)).unwrap(), start);
+        }
+    }
+
+    #[test]
+    fn decode_error_test() {
+        let jjy = JJY::new();
+        let valid = widths(&jjy, at(2023, 8, 20, 10, 15, 0));
+
+        let mut w = valid;
+        w[3] = Some(300);
+        assert!(JJY::decode(&w).is_err());
+
+        // flip the hour parity bit
+        let mut w = valid;
+        w[36] = Some(800);
+        assert!(JJY::decode(&w).is_err());
+
+        let mut w = valid;
+        w[19] = Some(800);
+        assert!(JJY::decode(&w).is_err());
+    }
+
+    #[test]
+    fn describe_test() {
+        let jjy = JJY::new();
+        assert_eq!(
+            jjy.describe(at(2023, 8, 20, 10, 15, 3)),
+            "10:15:03 [ 3] minute = One (500ms)"
+        );
+        assert_eq!(
+            jjy.describe(at(2023, 8, 20, 10, 15, 39)),
+            "10:15:39 [39] marker = Marker (200ms)"
+        );
+    }
+}
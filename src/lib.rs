@@ -0,0 +1,52 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+mod bpc;
+#[cfg(feature = "std")]
+pub mod clock;
+pub mod core;
+#[cfg(feature = "std")]
+mod dcf77;
+#[cfg(feature = "rodio")]
+pub mod device;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+mod jjy;
+#[cfg(feature = "std")]
+mod pattern;
+#[cfg(feature = "std")]
+pub mod signal;
+#[cfg(feature = "rodio")]
+mod source;
+#[cfg(feature = "spectrum")]
+pub mod spectrum;
+#[cfg(feature = "std")]
+mod wave;
+#[cfg(feature = "std")]
+pub mod wwvb;
+
+pub use crate::core::{BPC_FREQ, CODE_WIDTHS, FRAME_LEN, YEARS};
+#[cfg(feature = "std")]
+pub use bpc::{cst, now_in, Decoded, Frame, PulseWidth, ZonedDateTime, BPC};
+#[cfg(feature = "std")]
+pub use clock::{Clock, MockClock, SimulatedClock, SystemClock};
+#[cfg(feature = "std")]
+pub use dcf77::DCF77;
+#[cfg(feature = "std")]
+pub use jjy::JJY;
+#[cfg(feature = "std")]
+pub use pattern::TestPattern;
+#[cfg(feature = "std")]
+pub use signal::TimeSignal;
+#[cfg(feature = "rodio")]
+pub use source::BPCWave;
+#[cfg(feature = "std")]
+pub use wave::{
+    pulse, render, render_loop_wav, render_wav, BPCWaveBuilder, BuildError, Idle, Monitor,
+    SignalSamples, SignalWave, TransmitStatus, Waveform, DEFAULT_SAMPLE_RATE,
+};
+#[cfg(feature = "std")]
+pub use wwvb::WWVB;
@@ -1,25 +1,1397 @@
-mod bpc;
+use anyhow::{bail, ensure, Context, Result};
+use bpcsync::{
+    analysis, cst, device, render, render_loop_wav, render_wav, BPCWave, BPCWaveBuilder, Clock,
+    Idle, Monitor, PulseWidth, SignalSamples, SimulatedClock, TestPattern, TimeSignal,
+    TransmitStatus, Waveform, ZonedDateTime, BPC, BPC_FREQ, DCF77, DEFAULT_SAMPLE_RATE, FRAME_LEN,
+    JJY, WWVB, YEARS,
+};
+#[cfg(feature = "spectrum")]
+use bpcsync::{spectrum, SignalWave};
+use chrono::{DateTime, Datelike, Timelike};
+use clap::{builder::TypedValueParser, value_parser, Arg, ArgAction, ArgMatches};
+use log::{error, info, warn};
+#[cfg(feature = "spectrum")]
+use rodio::source::UniformSourceIterator;
+use rodio::{cpal::Host, Device, DeviceTrait, OutputStream, OutputStreamHandle, Sink};
+use std::{
+    io::{BufWriter, ErrorKind, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
 
-use std::sync::mpsc::channel;
-use anyhow::{Context, Result};
-use bpc::BPCWave;
-use ctrlc;
-use rodio::{OutputStream, Sink};
+mod config;
+
+use config::Config;
+
+// how often the playback is checked on
+const POLL_PERIOD: Duration = Duration::from_millis(100);
+// frames written to stdout at once by `--raw`
+const RAW_CHUNK: usize = 1024;
+// how far `--raw` may get ahead of real time
+const MAX_AHEAD: Duration = Duration::from_millis(200);
+// how long `stop` waits for the output to pull the whole fade out
+const FADE_LIMIT: Duration = Duration::from_millis(100);
+// how long the output is left to play what it buffered once faded out
+const FADE_TAIL: Duration = Duration::from_millis(30);
+// how long the update thread may go without waking up
+const WATCHDOG_LIMIT: Duration = Duration::from_secs(5);
+// how long the output may go without pulling samples before it is reopened
+const OUTPUT_LIMIT: Duration = Duration::from_secs(5);
+// how long a reopened output must play for its failed attempts to be forgotten
+const REOPEN_RESET: Duration = Duration::from_secs(60);
+// longest wait between two attempts to reopen the output
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// how far a measured width may be from its symbol, see `BPC::decode_tolerant`
+const WIDTH_TOLERANCE: u32 = 40;
+
+// the output stopped pulling samples, told apart from other failures of
+// `wait` so that the device can be reopened
+#[derive(Debug)]
+struct OutputLost(Duration);
+
+impl std::fmt::Display for OutputLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the output pulled no sample for {:?}", self.0)
+    }
+}
+
+impl std::error::Error for OutputLost {}
+
+// time code standards that can be transmitted
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Protocol {
+    Bpc,
+    Wwvb,
+    Dcf77,
+    Jjy,
+}
+
+impl Protocol {
+    fn signal(&self, carrier_khz: Option<u32>) -> Result<Box<dyn TimeSignal + Send>> {
+        Ok(match (self, carrier_khz) {
+            (Protocol::Jjy, Some(khz)) => Box::new(JJY::with_carrier_khz(khz)?),
+            (Protocol::Jjy, None) => Box::new(JJY::new()),
+            (_, Some(_)) => bail!("`--carrier-khz` only applies to jjy"),
+            (Protocol::Bpc, None) => Box::new(BPC::new()),
+            (Protocol::Wwvb, None) => Box::new(WWVB::new()),
+            (Protocol::Dcf77, None) => Box::new(DCF77::new()),
+        })
+    }
+
+    // the 2-bit code behind a symbol width, only bpc has any
+    fn code(&self, width_ms: Option<u32>) -> Option<u8> {
+        match self {
+            Protocol::Bpc => width_ms.and_then(PulseWidth::from_millis).map(|w| w.code()),
+            _ => None,
+        }
+    }
+
+    // frequency in Hz the real station broadcasts on
+    fn station_hz(&self, carrier_khz: Option<u32>) -> f32 {
+        match self {
+            Protocol::Bpc => BPC_FREQ as f32,
+            Protocol::Wwvb => 60000.,
+            Protocol::Dcf77 => 77500.,
+            Protocol::Jjy => carrier_khz.unwrap_or(40) as f32 * 1000.,
+        }
+    }
+}
+
+// sample layout of `--raw`: headerless interleaved frames of one sample per
+// channel, channel 0 first, each sample a little-endian f32 in [-1, 1] or,
+// with `--format i16`, a little-endian signed 16-bit integer
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RawFormat {
+    F32,
+    I16,
+}
+
+struct Frame {
+    channels: usize,
+    sample_rate: u32,
+}
+
+// what the tool does, one subcommand each
+#[derive(Clone, Debug, PartialEq)]
+enum Command {
+    // transmit through the sound card, the default
+    Play,
+    // render to a wav file instead of playing, with `looped` one that loops
+    // seamlessly over the current minute
+    Render {
+        path: PathBuf,
+        looped: bool,
+    },
+    // print the transmitted codes, or the spectrum, instead of playing them
+    Inspect,
+    // decode a rendered signal back, or with `recording` a recording whose
+    // first sample was taken at the time alongside
+    Verify {
+        recording: Option<(PathBuf, ZonedDateTime)>,
+    },
+    // list the output devices
+    Devices,
+}
+
+// every subcommand, `play` first since it is the default
+const COMMANDS: [&str; 5] = ["play", "render", "inspect", "verify", "devices"];
+
+struct Args {
+    command: Command,
+    protocol: Protocol,
+    // jjy station frequency, 40 or 60
+    carrier_khz: Option<u32>,
+    // play this subharmonic of the station frequency instead of the default
+    // tone
+    carrier_divisor: Option<u32>,
+    // write the samples to stdout instead of playing, see `RawFormat`
+    raw: Option<RawFormat>,
+    // defaults to the device rate, or `DEFAULT_SAMPLE_RATE` for `--raw`
+    sample_rate: Option<u32>,
+    // length of `render`
+    minutes: u32,
+    // output device index or name substring
+    device: Option<String>,
+    // audio backend, e.g. alsa or jack, see `device::backends`
+    backend: Option<String>,
+    // fail instead of warning when the device cannot carry the carrier
+    strict: bool,
+    gain: f32,
+    // scale a gain that would clip down instead of warning about it
+    normalize: bool,
+    // share of the carrier removed during a reduction
+    depth: f32,
+    waveform: Waveform,
+    // full carrier for the symbol width instead of reduced
+    invert: bool,
+    // what to emit during the frame marker: silence, full or reduced:DEPTH
+    idle: Idle,
+    // length of the envelope ramps
+    ramp_ms: Option<u64>,
+    // output latency to make up for
+    offset_ms: u64,
+    channels: u16,
+    // carrier phase of the channels after the first, in degrees
+    channel_phase: f32,
+    // print a json object for each second instead of the status line, see
+    // `StatusLine`
+    json_status: bool,
+    // stop playing after this many seconds, rounded up to a frame boundary
+    duration: Option<u32>,
+    // stop playing once this many whole frames are sent
+    max_frames: Option<u64>,
+    // times to try reopening the device once it stops pulling samples
+    reopen_attempts: u32,
+    // wait before the first attempt, doubled after each failed one up to
+    // `MAX_BACKOFF`
+    reopen_backoff_ms: u64,
+    // start transmitting at the top of the next minute
+    aligned: bool,
+    // send the next whole frame, then stop
+    once: bool,
+    // busy-wait the end of each second for tighter edges
+    precise_timing: bool,
+    // how long past each second the update thread wakes up
+    wake_margin_us: u64,
+    // a fixed pattern instead of the time, for checking that a receiver
+    // picks anything up; the tone stays that of `protocol`
+    test_tone: Option<TestPattern>,
+    // print the strongest tone of a second as the device would receive it,
+    // for `inspect`
+    spectrum: bool,
+    // subharmonics of the station frequency to cycle through
+    sweep: Option<Vec<u32>>,
+    // frames spent at each subharmonic of the sweep
+    sweep_frames: u32,
+    // transmit as if the clock read this time at startup
+    start: Option<ZonedDateTime>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            command: Command::Play,
+            protocol: Protocol::Bpc,
+            carrier_khz: None,
+            carrier_divisor: None,
+            raw: None,
+            sample_rate: None,
+            minutes: 1,
+            device: None,
+            backend: None,
+            strict: false,
+            gain: 1.,
+            normalize: false,
+            depth: 1.,
+            waveform: Waveform::Sine,
+            invert: false,
+            idle: Idle::FullCarrier,
+            ramp_ms: None,
+            offset_ms: 0,
+            channels: 1,
+            channel_phase: 0.,
+            json_status: false,
+            duration: None,
+            max_frames: None,
+            reopen_attempts: 3,
+            reopen_backoff_ms: 1000,
+            aligned: false,
+            once: false,
+            precise_timing: false,
+            wake_margin_us: 500,
+            test_tone: None,
+            spectrum: false,
+            sweep: None,
+            sweep_frames: 3,
+            start: None,
+        }
+    }
+}
+
+impl Args {
+    // what to transmit on the tone of `protocol`
+    fn signal(&self) -> Result<Box<dyn TimeSignal + Send>> {
+        let signal = self.protocol.signal(self.carrier_khz)?;
+        Ok(match self.test_tone {
+            Some(pattern) => Box::new(pattern),
+            None => signal,
+        })
+    }
+
+    // exits on a usage error, or after printing the help
+    fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1)).map_err(|e| match e.downcast::<clap::Error>() {
+            Ok(e) => e.exit(),
+            Err(e) => e,
+        })
+    }
+
+    fn parse_from(cli: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut argv: Vec<String> = cli.into_iter().collect();
+        // bare flags still play
+        let first = argv.first().map(String::as_str);
+        let named = ["help", "-h", "--help", "-V", "--version"];
+        if !first.is_some_and(|a| COMMANDS.contains(&a) || named.contains(&a)) {
+            argv.insert(0, "play".to_string());
+        }
+        let matches =
+            command().try_get_matches_from(std::iter::once("bpcsync".into()).chain(argv))?;
+        let (name, m) = matches.subcommand().context("missing subcommand")?;
+
+        let mut args = Args::default();
+        // the file comes first, so that flags override it
+        if let Some(path) = given::<PathBuf>(m, "config") {
+            Config::load(&path)?.apply(&mut args);
+        }
+        args.command = match name {
+            "render" => Command::Render {
+                path: given(m, "path").context("missing path")?,
+                looped: set(m, "loop"),
+            },
+            "inspect" => Command::Inspect,
+            "verify" => Command::Verify {
+                recording: given(m, "wav").zip(given(m, "at")),
+            },
+            "devices" => Command::Devices,
+            _ => Command::Play,
+        };
+        if let Some(v) = given(m, "protocol") {
+            args.protocol = v;
+        }
+        args.carrier_khz = given(m, "carrier-khz").or(args.carrier_khz);
+        args.carrier_divisor = given(m, "carrier-divisor").or(args.carrier_divisor);
+        args.raw = given(m, "format").or(set(m, "raw").then_some(RawFormat::F32));
+        args.sample_rate = given(m, "sample-rate").or(args.sample_rate);
+        args.minutes = given(m, "minutes").unwrap_or(args.minutes);
+        args.device = given(m, "device").or(args.device.take());
+        args.backend = given(m, "backend").or(args.backend.take());
+        args.strict |= set(m, "strict");
+        args.gain = given(m, "gain").unwrap_or(args.gain);
+        args.normalize |= set(m, "normalize");
+        args.depth = given(m, "modulation-depth").unwrap_or(args.depth);
+        args.waveform = given(m, "waveform").unwrap_or(args.waveform);
+        args.invert |= set(m, "invert");
+        args.idle = given(m, "idle").unwrap_or(args.idle);
+        args.ramp_ms = given(m, "ramp-ms").or(args.ramp_ms);
+        args.offset_ms = given(m, "offset").unwrap_or(args.offset_ms);
+        args.channels = given(m, "channels").unwrap_or(args.channels);
+        args.channel_phase = given(m, "channel-phase").unwrap_or(args.channel_phase);
+        args.json_status |= set(m, "json-status");
+        args.duration = given(m, "duration");
+        args.max_frames = given(m, "max-frames");
+        args.reopen_attempts = given(m, "reopen-attempts").unwrap_or(args.reopen_attempts);
+        args.reopen_backoff_ms = given(m, "reopen-backoff-ms").unwrap_or(args.reopen_backoff_ms);
+        args.aligned |= set(m, "aligned");
+        args.once = set(m, "once");
+        args.precise_timing |= set(m, "precise-timing");
+        args.wake_margin_us = given(m, "wake-margin-us").unwrap_or(args.wake_margin_us);
+        args.test_tone = given(m, "test-tone");
+        args.spectrum = set(m, "spectrum");
+        args.sweep = m
+            .try_get_many::<u32>("sweep")
+            .ok()
+            .flatten()
+            .map(|divisors| divisors.copied().collect());
+        args.sweep_frames = given(m, "sweep-frames").unwrap_or(args.sweep_frames);
+        args.start = given(m, "start");
+
+        // the file may set the divisor too
+        ensure!(
+            args.carrier_divisor.is_none() || args.sweep.is_none(),
+            "`--carrier-divisor` cannot be combined with `--sweep`"
+        );
+        if args.once {
+            args.max_frames = Some(1);
+        }
+        Ok(args)
+    }
+}
+
+// the subcommands and the options each one takes, named after the keys of
+// `Config`
+fn command() -> clap::Command {
+    let signal = || {
+        [
+            option(
+                "config",
+                "PATH",
+                "Read settings from a TOML file, flags override it",
+            )
+            .value_parser(value_parser!(PathBuf)),
+            option(
+                "protocol",
+                "NAME",
+                "Time code: bpc, wwvb, dcf77 or jjy [default: bpc]",
+            )
+            .value_parser(parser(protocol)),
+            option(
+                "carrier-khz",
+                "KHZ",
+                "Station frequency of jjy, 40 or 60 [default: 40]",
+            )
+            .value_parser(value_parser!(u32)),
+            option(
+                "start",
+                "TIME",
+                "Transmit as if the clock read this RFC 3339 time",
+            )
+            .value_parser(parser(rfc3339)),
+        ]
+    };
+    let test_tone = || {
+        option(
+            "test-tone",
+            "PATTERN",
+            "Send steady, alternating or staircase pulses instead of the time",
+        )
+        .value_parser(parser(test_pattern))
+    };
+    let output = || {
+        [
+            option(
+                "device",
+                "NAME",
+                "Output device index or name substring, see `devices`",
+            ),
+            option(
+                "backend",
+                "NAME",
+                "Audio backend, e.g. alsa, or jack with the jack feature",
+            ),
+            option(
+                "sample-rate",
+                "HZ",
+                "Sample rate [default: the device rate]",
+            )
+            .value_parser(value_parser!(u32).range(1..)),
+            option(
+                "carrier-divisor",
+                "N",
+                "Play this subharmonic of the station frequency instead of the default tone",
+            )
+            .value_parser(value_parser!(u32).range(1..)),
+        ]
+    };
+    let backend = output()[1].clone();
+
+    let play = clap::Command::new("play")
+        .about("Transmit the time through the sound card, the default")
+        .args(signal())
+        .arg(test_tone().conflicts_with("sweep"))
+        .args(output())
+        .args([
+            switch(
+                "strict",
+                "Fail instead of warning when the device cannot carry the carrier",
+            ),
+            option(
+                "gain",
+                "GAIN",
+                "Factor applied to every sample [default: 1]",
+            )
+            .value_parser(value_parser!(f32)),
+            switch(
+                "normalize",
+                "Scale a gain that would clip down to full scale",
+            ),
+            option(
+                "modulation-depth",
+                "DEPTH",
+                "Share of the carrier removed during a reduction [default: 1]",
+            )
+            .value_parser(value_parser!(f32)),
+            option(
+                "waveform",
+                "SHAPE",
+                "Carrier shape, sine or square [default: sine]",
+            )
+            .value_parser(parser(waveform)),
+            switch(
+                "invert",
+                "Full carrier for the symbol width instead of reduced",
+            ),
+            option(
+                "idle",
+                "IDLE",
+                "Silence, full or reduced:DEPTH during the frame marker [default: full]",
+            )
+            .value_parser(parser(idle)),
+            option("ramp-ms", "MS", "Length of the envelope ramps [default: 2]")
+                .value_parser(value_parser!(u64)),
+            option("offset", "MS", "Output latency to make up for [default: 0]")
+                .value_parser(value_parser!(u64)),
+            option("channels", "N", "Output channels [default: 1]")
+                .value_parser(value_parser!(u16)),
+            option(
+                "channel-phase",
+                "DEGREES",
+                "Carrier phase of the channels after the first [default: 0]",
+            )
+            .value_parser(value_parser!(f32)),
+            switch("json-status", "Print a json object for each second")
+                .conflicts_with_all(["raw", "format"]),
+            option(
+                "duration",
+                "SECONDS",
+                "Stop at the end of the frame this falls in",
+            )
+            .value_parser(value_parser!(u32)),
+            option(
+                "max-frames",
+                "N",
+                "Stop once this many whole frames are sent",
+            )
+            .value_parser(value_parser!(u64)),
+            option(
+                "reopen-attempts",
+                "N",
+                "Times to try reopening a device that stopped pulling samples [default: 3]",
+            )
+            .value_parser(value_parser!(u32)),
+            option(
+                "reopen-backoff-ms",
+                "MS",
+                "Wait before the first attempt, doubled after each failed one [default: 1000]",
+            )
+            .value_parser(value_parser!(u64)),
+            switch("aligned", "Start at the top of the next minute"),
+            switch("once", "Send the next whole frame, then stop")
+                .conflicts_with_all(["max-frames", "sweep"]),
+            switch("precise-timing", "Busy-wait the end of each second"),
+            option(
+                "wake-margin-us",
+                "US",
+                "How long past each second the update thread wakes up [default: 500]",
+            )
+            .value_parser(value_parser!(u64)),
+            option(
+                "sweep",
+                "DIVISORS",
+                "Cycle through these subharmonics, e.g. 5,7,9",
+            )
+            .value_delimiter(',')
+            .value_parser(value_parser!(u32).range(1..)),
+            option(
+                "sweep-frames",
+                "N",
+                "Frames spent at each subharmonic [default: 3]",
+            )
+            .value_parser(value_parser!(u32).range(1..)),
+            switch("raw", "Write the samples to stdout instead of playing them"),
+            option(
+                "format",
+                "FORMAT",
+                "Sample format of `--raw`, f32 or i16 [default: f32]",
+            )
+            .value_parser(parser(raw_format)),
+        ]);
+    let render = clap::Command::new("render")
+        .about("Render the signal to a wav file instead of playing it")
+        .arg(
+            Arg::new("path")
+                .value_name("PATH")
+                .required(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("Wav file to write"),
+        )
+        .args(signal())
+        .arg(test_tone())
+        .args([
+            option("minutes", "N", "Minutes to render [default: 1]")
+                .value_parser(value_parser!(u32))
+                .conflicts_with("loop"),
+            switch(
+                "loop",
+                "Render the current minute so that it loops seamlessly",
+            ),
+        ]);
+    let inspect = clap::Command::new("inspect")
+        .about("Print what is transmitted each second instead of playing it")
+        .args(signal())
+        .arg(test_tone())
+        .args(output())
+        .args([
+            switch("json-status", "Print a json object for each second"),
+            switch(
+                "spectrum",
+                "Print the strongest tone of a second as the device would receive it",
+            ),
+        ]);
+    let verify = clap::Command::new("verify")
+        .about("Decode a rendered signal, or a recording, and compare it with the time")
+        .args(signal())
+        .args([
+            option(
+                "wav",
+                "PATH",
+                "Recording to decode instead of a rendered signal",
+            )
+            .value_parser(value_parser!(PathBuf))
+            .requires("at"),
+            option(
+                "at",
+                "TIME",
+                "RFC 3339 time of the first sample of the recording",
+            )
+            .value_parser(parser(rfc3339))
+            .requires("wav"),
+        ]);
+    let devices = clap::Command::new("devices")
+        .about("List the output devices")
+        .arg(signal()[0].clone())
+        .arg(backend);
+
+    clap::Command::new("bpcsync")
+        .about("Transmits BPC and other radio time signals through a sound card")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .subcommands([play, render, inspect, verify, devices])
+}
+
+// an option taking a value, read back with `given`
+fn option(name: &'static str, value_name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name).long(name).value_name(value_name).help(help)
+}
+
+// a flag, read back with `set`
+fn switch(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name)
+        .long(name)
+        .action(ArgAction::SetTrue)
+        .help(help)
+}
+
+// the value of an option, `None` when not given or not taken by the
+// subcommand
+fn given<T: Clone + Send + Sync + 'static>(m: &ArgMatches, id: &str) -> Option<T> {
+    m.try_get_one::<T>(id).ok().flatten().cloned()
+}
+
+fn set(m: &ArgMatches, id: &str) -> bool {
+    given(m, id).unwrap_or(false)
+}
+
+// one of the parsing functions below as a value parser
+fn parser<T: Clone + Send + Sync + 'static>(
+    f: fn(&str) -> Result<T>,
+) -> impl TypedValueParser<Value = T> {
+    move |v: &str| f(v).map_err(|e| format!("{e:#}"))
+}
+
+fn protocol(name: &str) -> Result<Protocol> {
+    Ok(match name {
+        "bpc" => Protocol::Bpc,
+        "wwvb" => Protocol::Wwvb,
+        "dcf77" => Protocol::Dcf77,
+        "jjy" => Protocol::Jjy,
+        p => bail!("unknown protocol `{p}`"),
+    })
+}
+
+fn waveform(name: &str) -> Result<Waveform> {
+    Ok(match name {
+        "sine" => Waveform::Sine,
+        "square" => Waveform::Square,
+        w => bail!("unknown waveform `{w}`"),
+    })
+}
+
+fn idle(name: &str) -> Result<Idle> {
+    Ok(match name.split_once(':') {
+        None if name == "silence" => Idle::Silence,
+        None if name == "full" => Idle::FullCarrier,
+        Some(("reduced", depth)) => Idle::ReducedCarrier(
+            depth
+                .parse()
+                .with_context(|| format!("invalid idle reduction `{depth}`"))?,
+        ),
+        _ => bail!("unknown idle behavior `{name}`, expected silence, full or reduced:DEPTH"),
+    })
+}
+
+fn test_pattern(name: &str) -> Result<TestPattern> {
+    Ok(match name {
+        "steady" => TestPattern::Steady,
+        "alternating" => TestPattern::Alternating,
+        "staircase" => TestPattern::Staircase,
+        p => bail!("unknown test pattern `{p}`"),
+    })
+}
+
+fn raw_format(name: &str) -> Result<RawFormat> {
+    Ok(match name {
+        "f32" => RawFormat::F32,
+        "i16" => RawFormat::I16,
+        f => bail!("unknown sample format `{f}`"),
+    })
+}
+
+// e.g. 2023-12-31T23:59:58+08:00
+fn rfc3339(v: &str) -> Result<ZonedDateTime> {
+    DateTime::parse_from_rfc3339(v).with_context(|| format!("invalid rfc 3339 time `{v}`"))
+}
+
+fn output_device(host: &Host, name: Option<&str>) -> Result<Device> {
+    if let Some(name) = name {
+        match device::find_output_device(host, name)? {
+            Some(device) => return Ok(device),
+            None => warn!("output device `{name}` not found, using default"),
+        }
+    }
+    device::preferred_output_device(host).context("no working output device")
+}
+
+// opens the device at its native sample rate, which is returned alongside
+// the highest one it supports
+fn open_output(
+    host: &Host,
+    name: Option<&str>,
+) -> Result<(OutputStream, OutputStreamHandle, u32, u32)> {
+    let device = output_device(host, name)?;
+    let device_name = device.name().unwrap_or_default();
+    let config = device
+        .default_output_config()
+        .with_context(|| format!("unable to query output device {device_name}"))?;
+    let sample_rate = config.sample_rate().0;
+    let max_rate = device::max_sample_rate(&device).unwrap_or(sample_rate);
+    let (stream, handle) = OutputStream::try_from_device_config(&device, config)
+        .with_context(|| format!("unable to open output device {device_name}"))?;
+    info!("opened output device {device_name} at {sample_rate} Hz, up to {max_rate} Hz");
+    Ok((stream, handle, sample_rate, max_rate))
+}
+
+// warns, or fails when `strict`, if the output cannot carry `carrier` and
+// suggests a way out
+fn check_bandwidth(
+    carrier: f32,
+    station: f32,
+    sample_rate: u32,
+    max_rate: u32,
+    strict: bool,
+) -> Result<()> {
+    let problem = if carrier >= max_rate as f32 / 2. {
+        let too_little = format!(
+            "the output device supports at most {max_rate} Hz, too little for a {carrier:.0} Hz \
+             carrier"
+        );
+        match device::lowest_divisor(station, max_rate) {
+            Some(d) => format!(
+                "{too_little}; try the 1/{d} subharmonic of {station} Hz with \
+                 `--carrier-divisor {d}`"
+            ),
+            None => format!("{too_little}, or for any subharmonic of {station} Hz"),
+        }
+    } else if carrier >= sample_rate as f32 / 2. {
+        format!(
+            "{sample_rate} Hz is too low a sample rate for a {carrier:.0} Hz carrier; \
+             the output device supports up to `--sample-rate {max_rate}`"
+        )
+    } else {
+        return Ok(());
+    };
+    ensure!(!strict, "{problem}");
+    warn!("{problem}");
+    Ok(())
+}
+
+// renders a few frames from now, detects their pulse widths and decodes them
+fn verify(now: ZonedDateTime) -> Result<()> {
+    let bpc = BPC::new();
+    let start = bpc.frame_start(now);
+    let samples: Vec<f32> = render(&bpc, start, Duration::from_secs(60)).collect();
+    let widths = analysis::pulse_widths(&samples, DEFAULT_SAMPLE_RATE);
+
+    let frame_len = bpc.frame_len_secs();
+    let mut pass = true;
+    for (i, frame) in widths.chunks_exact(frame_len as usize).enumerate() {
+        let expected = start + chrono::Duration::seconds(i as i64 * frame_len as i64);
+        match BPC::decode_tolerant(frame.try_into()?, WIDTH_TOLERANCE) {
+            Ok(d) if d.time == expected => println!(
+                "PASS decoded {expected}, widths off by {}ms at most",
+                d.max_error_ms
+            ),
+            Ok(d) => {
+                pass = false;
+                println!("FAIL decoded {}, expected {expected}", d.time);
+            }
+            Err(e) => {
+                pass = false;
+                println!("FAIL {e:#}, expected {expected}");
+            }
+        }
+    }
+    ensure!(pass, "decoded time does not match the source clock");
+    Ok(())
+}
+
+// decodes every whole frame of a recording whose first sample was taken at
+// `at`, and compares each one with the time it was sent at
+fn check_wav(path: &Path, at: ZonedDateTime) -> Result<()> {
+    let (samples, sample_rate) = analysis::read_wav(path)?;
+    let recording = analysis::recorded_widths(&samples, sample_rate)
+        .with_context(|| format!("no pulse found in {}", path.display()))?;
+    let widths = recording.widths;
+    // when the first second starts, to the nearest second
+    let ms = (recording.offset as u64 * 1000 / sample_rate as u64) as i64 + 500;
+    let first = (at + chrono::Duration::milliseconds(ms))
+        .with_nanosecond(0)
+        .unwrap();
+    info!(
+        "{} s at {sample_rate} Hz, seconds start {} samples in",
+        widths.len(),
+        recording.offset
+    );
+
+    let mut frames = 0;
+    let mut pass = true;
+    let mut start = 0;
+    while start + FRAME_LEN <= widths.len() {
+        // a frame starts on a marker, a pulse right after it
+        if widths[start].is_some() || widths[start + 1].is_none() {
+            start += 1;
+            continue;
+        }
+        let expected = first + chrono::Duration::seconds(start as i64);
+        frames += 1;
+        let frame = widths[start..start + FRAME_LEN].try_into()?;
+        match BPC::decode_tolerant(frame, WIDTH_TOLERANCE) {
+            Ok(d) if d.time == expected => println!(
+                "PASS decoded {expected}, widths off by {}ms at most",
+                d.max_error_ms
+            ),
+            Ok(d) => {
+                pass = false;
+                println!(
+                    "FAIL decoded {}, expected {expected}: {}",
+                    d.time,
+                    diff(d.time, expected)
+                );
+            }
+            Err(e) => {
+                pass = false;
+                println!(
+                    "FAIL frame at second {start} of the recording: {e:#}, expected {expected}"
+                );
+            }
+        }
+        start += FRAME_LEN;
+    }
+    ensure!(frames > 0, "no whole frame in {}", path.display());
+    ensure!(
+        pass,
+        "decoded time does not match the one given with `--at`"
+    );
+    Ok(())
+}
+
+// the fields of `decoded` that differ from `expected`
+fn diff(decoded: ZonedDateTime, expected: ZonedDateTime) -> String {
+    let expected = expected.with_timezone(decoded.offset());
+    let fields = [
+        ("year", decoded.year() as u32, expected.year() as u32),
+        ("month", decoded.month(), expected.month()),
+        ("day", decoded.day(), expected.day()),
+        ("hour", decoded.hour(), expected.hour()),
+        ("minute", decoded.minute(), expected.minute()),
+        ("second", decoded.second(), expected.second()),
+    ];
+    fields
+        .iter()
+        .filter(|(_, d, e)| d != e)
+        .map(|(name, d, e)| format!("{name} {d} instead of {e}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// renders a second at `sample_rate`, converts it to the device rate the way
+// rodio does before playing it and prints its strongest tone
+#[cfg(feature = "spectrum")]
+fn print_spectrum(
+    signal: &impl TimeSignal,
+    carrier: f32,
+    sample_rate: u32,
+    native_rate: u32,
+    now: ZonedDateTime,
+) -> Result<()> {
+    let wave = SignalWave::new(signal, sample_rate, now).with_carrier(carrier);
+    let samples: Vec<f32> = UniformSourceIterator::<_, f32>::new(wave, 1, native_rate)
+        .take(native_rate as usize)
+        .collect();
+    let peak = spectrum::peak(&samples, native_rate).context("no samples rendered")?;
+    println!(
+        "peak at {:.1} Hz, magnitude {:.3}, rendered at {sample_rate} Hz, played at {native_rate} Hz",
+        peak.freq_hz, peak.magnitude
+    );
+    // a few bins of leeway
+    if (peak.freq_hz - carrier).abs() > 10. {
+        println!(
+            "WARN expected the carrier at {carrier:.1} Hz, the output path filters or \
+             resamples it away; try a larger `--carrier-divisor`"
+        );
+    }
+    Ok(())
+}
+
+// one line of `--json-status`
+#[derive(serde::Serialize)]
+struct StatusLine {
+    // the instant the code was computed from
+    ts: ZonedDateTime,
+    // index of the second within the frame
+    second: u32,
+    code: Option<u8>,
+    width_ms: Option<u32>,
+}
+
+impl StatusLine {
+    fn at(signal: &impl TimeSignal, protocol: Protocol, t: ZonedDateTime) -> Self {
+        let width_ms = signal.symbol_width(t);
+        Self {
+            ts: t,
+            second: t.second() % signal.frame_len_secs(),
+            code: protocol.code(width_ms),
+            width_ms,
+        }
+    }
+
+    fn print(&self) -> Result<()> {
+        println!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
+// rewrites one terminal line with each newly transmitted second, or prints a
+// `StatusLine` for it with `json`
+struct Progress {
+    enabled: bool,
+    json: Option<Protocol>,
+    last: Option<ZonedDateTime>,
+}
+
+impl Progress {
+    fn show(&mut self, status: TransmitStatus) -> Result<()> {
+        let Some(ts) = status.time.filter(|_| status.time != self.last) else {
+            return Ok(());
+        };
+        self.last = status.time;
+        if let Some(protocol) = self.json {
+            let line = StatusLine {
+                ts,
+                second: status.second,
+                code: protocol.code(status.width),
+                width_ms: status.width,
+            };
+            return line.print();
+        }
+        if !self.enabled {
+            return Ok(());
+        }
+        let time = status.time.unwrap().format("%Y-%m-%d %H:%M:%S");
+        let width = status
+            .width
+            .map_or("marker".to_string(), |w| format!("{w}ms"));
+        print!("\r{time} second {:>2} {width:<6}", status.second);
+        _ = std::io::stdout().flush();
+        Ok(())
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.enabled && self.last.is_some() {
+            println!();
+        }
+    }
+}
+
+// waits for ctrl+c, returning true, or for `timeout` to elapse or
+// `max_frames` whole frames to be sent, returning false, while checking that
+// the update thread is still running; `json` prints each second for
+// `--json-status`
+fn wait(
+    rx: &Receiver<()>,
+    monitor: &Monitor,
+    timeout: Option<Duration>,
+    max_frames: Option<u64>,
+    json: Option<Protocol>,
+) -> Result<bool> {
+    let end = timeout.map(|t| Instant::now() + t);
+    // a status line for whoever watches the terminal, unless logs already
+    // tell them
+    let mut progress = Progress {
+        enabled: std::io::stdout().is_terminal()
+            && !log::log_enabled!(log::Level::Info)
+            && json.is_none(),
+        json,
+        last: None,
+    };
+    loop {
+        progress.show(monitor.status())?;
+        let left = end.map_or(POLL_PERIOD, |end| {
+            end.saturating_duration_since(Instant::now())
+        });
+        match rx.recv_timeout(left.min(POLL_PERIOD)) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => return Ok(true),
+        }
+        let stale = monitor.since_last_update();
+        if stale > WATCHDOG_LIMIT {
+            error!("no update for {stale:?}, the generator is stuck");
+            bail!("the update thread stopped advancing");
+        }
+        let silent = monitor.since_last_pull();
+        if silent > OUTPUT_LIMIT {
+            error!("no sample pulled for {silent:?}, the output device may be gone");
+            return Err(OutputLost(silent).into());
+        }
+        if end.is_some_and(|end| Instant::now() >= end) {
+            return Ok(false);
+        }
+        if max_frames.is_some_and(|n| monitor.status().frames >= n) {
+            return Ok(false);
+        }
+    }
+}
+
+// writes samples to stdout until ctrl+c, the end of `timeout` or `max_frames`
+// whole frames, then fades out, or until the reader closes the pipe, paced to
+// stay at most `MAX_AHEAD` ahead of real time
+fn write_raw(
+    mut samples: SignalSamples,
+    format: RawFormat,
+    frame: Frame,
+    rx: &Receiver<()>,
+    timeout: Option<Duration>,
+    max_frames: Option<u64>,
+) -> Result<()> {
+    let monitor = samples.monitor();
+    let started = Instant::now();
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    let mut buf = Vec::new();
+    let mut written = 0;
+    info!("writing raw samples at {} Hz", frame.sample_rate);
+    let mut stopping = false;
+    while !monitor.faded() {
+        if !stopping
+            && (!matches!(rx.try_recv(), Err(TryRecvError::Empty))
+                || timeout.is_some_and(|t| started.elapsed() >= t)
+                || max_frames.is_some_and(|n| monitor.status().frames >= n))
+        {
+            // end the stream on silence rather than mid-cycle
+            monitor.fade_out();
+            stopping = true;
+        }
+        let stale = monitor.since_last_update();
+        if stale > WATCHDOG_LIMIT {
+            error!("no update for {stale:?}, the generator is stuck");
+            bail!("the update thread stopped advancing");
+        }
+
+        buf.clear();
+        for sample in samples.by_ref().take(RAW_CHUNK * frame.channels) {
+            match format {
+                RawFormat::F32 => buf.extend(sample.to_le_bytes()),
+                RawFormat::I16 => buf.extend(((sample * i16::MAX as f32) as i16).to_le_bytes()),
+            }
+        }
+        match out.write_all(&buf) {
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => return Ok(()),
+            r => r.context("failed to write to stdout")?,
+        }
+        written += RAW_CHUNK as u32;
+
+        // a reader that never blocks, like a file, would outrun the clock
+        let sent = Duration::from_secs(1) * written / frame.sample_rate;
+        let ahead = sent.saturating_sub(started.elapsed());
+        if ahead > MAX_AHEAD {
+            thread::sleep(ahead - MAX_AHEAD);
+        }
+    }
+    match out.flush() {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+        r => r.context("failed to write to stdout")?,
+    }
+    info!("{} whole frames written", monitor.status().frames);
+    Ok(())
+}
+
+// fades the carrier out before stopping the sink, so the speaker does not pop
+fn stop(sink: &Sink, monitor: &Monitor) {
+    monitor.fade_out();
+    let end = Instant::now() + FADE_LIMIT;
+    while !monitor.faded() && Instant::now() < end {
+        thread::sleep(Duration::from_millis(1));
+    }
+    // the device still has the end of the fade buffered
+    thread::sleep(FADE_TAIL);
+    sink.stop();
+}
 
 fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse()?;
+
+    if args.command == Command::Devices {
+        let host = device::host(args.backend.as_deref());
+        let devices =
+            device::output_devices(&host).context("unable to enumerate output devices")?;
+        for (i, device) in devices.iter().enumerate() {
+            println!("{i}: {}", device.name().unwrap_or_default());
+        }
+        return Ok(());
+    }
+
+    let signal = args.signal()?;
+    let station = args.protocol.station_hz(args.carrier_khz);
+    let carrier = match args.carrier_divisor {
+        Some(d) => station / d as f32,
+        None => args.protocol.signal(args.carrier_khz)?.carrier_hz(),
+    };
+    let clock = args.start.map(SimulatedClock::starting_at);
+    let now = || clock.as_ref().map_or_else(cst, Clock::now);
+    if matches!(args.protocol, Protocol::Bpc) && args.test_tone.is_none() {
+        let year = now().year();
+        ensure!(
+            u16::try_from(year).is_ok_and(|y| YEARS.contains(&y)),
+            "bpc cannot carry the year {year}, only {} - {}",
+            YEARS.start(),
+            YEARS.end()
+        );
+    }
+
+    if let Command::Verify { recording } = &args.command {
+        ensure!(
+            matches!(args.protocol, Protocol::Bpc),
+            "`verify` only supports bpc"
+        );
+        return match recording {
+            Some((path, at)) => check_wav(path, *at),
+            None => verify(now()),
+        };
+    }
+
+    if let Command::Render { path, looped } = &args.command {
+        let rendered = match looped {
+            true => render_loop_wav(&signal, path, now()),
+            false => {
+                let duration = Duration::from_secs(args.minutes as u64 * 60);
+                render_wav(&signal, path, now(), duration)
+            }
+        };
+        return rendered.with_context(|| format!("failed to render {}", path.display()));
+    }
+
     let (tx, rx) = channel();
     ctrlc::set_handler(move || _ = tx.send(()))?;
 
-    let (_stream, stream_handle) = OutputStream::try_default()
-        .with_context(|| format!("unable to open default output device"))?;
-    let sink = Sink::try_new(&stream_handle).with_context(|| format!("failed to create sink"))?;
+    if args.command == Command::Inspect && !args.spectrum {
+        loop {
+            // chrono counts a leap second past 1_000_000us
+            let delta = 1_000_000 - now().timestamp_subsec_micros() % 1_000_000;
+            match rx.recv_timeout(Duration::from_micros(delta as u64)) {
+                Err(RecvTimeoutError::Timeout) => {
+                    // the same instant for the code and its timestamp
+                    let t = now();
+                    match args.json_status {
+                        true => StatusLine::at(&signal, args.protocol, t).print()?,
+                        false => println!("{}", signal.describe(t)),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
 
-    let source = BPCWave::new();
-    sink.append(source);
+    match args.carrier_divisor {
+        Some(d) => info!("carrier {carrier:.1} Hz, 1/{d} of {station} Hz"),
+        None => info!("carrier {carrier:.1} Hz"),
+    }
+    let builder = |sample_rate| -> Result<BPCWaveBuilder> {
+        let mut builder = BPCWave::builder()
+            .signal(args.signal()?)
+            .carrier(carrier)
+            .sample_rate(sample_rate)
+            .gain(args.gain)
+            .normalize(args.normalize)
+            .modulation_depth(args.depth)
+            .waveform(args.waveform)
+            .invert(args.invert)
+            .idle(args.idle)
+            .aligned(args.aligned)
+            .frame_aligned(args.once)
+            .precise_timing(args.precise_timing)
+            .wake_margin(Duration::from_micros(args.wake_margin_us))
+            .offset(Duration::from_millis(args.offset_ms))
+            .channels(args.channels)
+            .channel_phase(args.channel_phase);
+        if let Some(ms) = args.ramp_ms {
+            builder = builder.ramp(Duration::from_millis(ms));
+        }
+        if let Some(clock) = clock {
+            builder = builder.clock(clock);
+        }
+        Ok(builder)
+    };
 
-    sink.play();
-    rx.recv()?;
-    sink.stop();
+    // either ctrl+c or the end of the frame `--duration` falls in
+    let timeout = || -> Result<Option<Duration>> {
+        let Some(seconds) = args.duration else {
+            return Ok(None);
+        };
+        let now = now();
+        let end = signal.next_frame_start(now + chrono::Duration::seconds(seconds as i64));
+        Ok(Some((end - now).to_std()?))
+    };
+
+    if args.aligned {
+        info!("transmitting from {}", signal.next_minute_start(now()));
+    } else if args.once {
+        info!("transmitting from {}", signal.next_frame_start(now()));
+    }
+
+    if let Some(format) = args.raw {
+        let sample_rate = args.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
+        let samples = builder(sample_rate)?
+            .build_samples()
+            .context("invalid wave configuration")?;
+        let frame = Frame {
+            channels: args.channels as usize,
+            sample_rate,
+        };
+        return write_raw(samples, format, frame, &rx, timeout()?, args.max_frames);
+    }
+
+    // `render`, `inspect` without `--spectrum` and `--raw` never get here,
+    // so they work without any device
+    let host = device::host(args.backend.as_deref());
+    let (stream, stream_handle, native_rate, max_rate) = open_output(&host, args.device.as_deref())
+        .context(
+            "no usable audio output, check `bpcsync devices` and pick one with `--device`, \
+         or run without audio using `render` or `inspect`",
+        )?;
+    // rodio resamples anything else to the native rate
+    let sample_rate = args.sample_rate.unwrap_or(native_rate);
+
+    if args.spectrum {
+        #[cfg(not(feature = "spectrum"))]
+        bail!("`--spectrum` needs a build with the `spectrum` feature");
+        #[cfg(feature = "spectrum")]
+        return print_spectrum(&signal, carrier, sample_rate, native_rate, now());
+    }
+
+    if let Some(divisors) = &args.sweep {
+        // reject every unusable subharmonic before transmitting any
+        for d in divisors {
+            let carrier = station / *d as f32;
+            check_bandwidth(carrier, station, sample_rate, max_rate, args.strict)?;
+            builder(sample_rate)?
+                .carrier(carrier)
+                .build_samples()
+                .with_context(|| format!("cannot sweep through 1/{d} of {station} Hz"))?;
+        }
+        let frame_len = chrono::Duration::seconds(signal.frame_len_secs() as i64);
+        for d in divisors.iter().cycle() {
+            let carrier = station / *d as f32;
+            let sink = Sink::try_new(&stream_handle).context("failed to create sink")?;
+            let source = builder(sample_rate)?
+                .carrier(carrier)
+                .build()
+                .context("invalid wave configuration")?;
+            let monitor = source.monitor();
+            sink.append(source);
+            sink.play();
+            info!(
+                "sweep: transmitting on 1/{d} of {station} Hz, {carrier:.0} Hz, for {} frames",
+                args.sweep_frames
+            );
+            let now = now();
+            let end = signal.frame_start(now) + frame_len * args.sweep_frames as i32;
+            let json = args.json_status.then_some(args.protocol);
+            let interrupted = wait(&rx, &monitor, Some((end - now).to_std()?), None, json)?;
+            stop(&sink, &monitor);
+            if interrupted {
+                break;
+            }
+        }
+        info!("shutting down");
+        return Ok(());
+    }
+
+    check_bandwidth(carrier, station, sample_rate, max_rate, args.strict)?;
+    // the same end and frame count whatever happens to the device
+    let end = timeout()?.map(|t| Instant::now() + t);
+    let json = args.json_status.then_some(args.protocol);
+    let mut output = (stream, stream_handle);
+    let mut frames = 0;
+    let mut failures = 0;
+    loop {
+        let sink = Sink::try_new(&output.1).context("failed to create sink")?;
+        let source = builder(sample_rate)?
+            .build()
+            .context("invalid wave configuration")?;
+        let monitor = source.monitor();
+        sink.append(source);
+
+        sink.play();
+        info!("stream started");
+        let started = Instant::now();
+        let left = end.map(|end| end.saturating_duration_since(started));
+        let max_frames = args.max_frames.map(|n| n.saturating_sub(frames));
+        let result = wait(&rx, &monitor, left, max_frames, json);
+        frames += monitor.status().frames;
+        match result {
+            Err(e) if e.is::<OutputLost>() => {}
+            result => {
+                result?;
+                info!("{frames} whole frames transmitted");
+                stop(&sink, &monitor);
+                break;
+            }
+        }
+
+        // a usb dac dropping out for a moment should not end an overnight
+        // session; the stream lets go of the device before it is reopened
+        drop(sink);
+        drop(output);
+        if started.elapsed() >= REOPEN_RESET {
+            failures = 0;
+        }
+        output = loop {
+            ensure!(
+                failures < args.reopen_attempts,
+                "the output device is gone, giving up after {failures} attempts to reopen it"
+            );
+            let backoff = Duration::from_millis(args.reopen_backoff_ms)
+                .saturating_mul(2u32.saturating_pow(failures))
+                .min(MAX_BACKOFF);
+            failures += 1;
+            warn!(
+                "reopening the output device in {backoff:?}, attempt {failures} of {}",
+                args.reopen_attempts
+            );
+            if !matches!(rx.recv_timeout(backoff), Err(RecvTimeoutError::Timeout)) {
+                info!("shutting down");
+                return Ok(());
+            }
+            match open_output(&host, args.device.as_deref()) {
+                Ok((stream, handle, _, _)) => break (stream, handle),
+                Err(e) => warn!("{e:#}"),
+            }
+        };
+    }
+    info!("shutting down");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(flags: &[&str]) -> Result<Args> {
+        Args::parse_from(flags.iter().map(|f| f.to_string()))
+    }
+
+    #[test]
+    fn command_test() {
+        command().debug_assert();
+
+        // bare flags play
+        assert_eq!(parse(&[]).unwrap().command, Command::Play);
+        let args = parse(&["--gain", "0.5", "--raw"]).unwrap();
+        assert_eq!(args.command, Command::Play);
+        assert_eq!((args.gain, args.raw), (0.5, Some(RawFormat::F32)));
+        assert_eq!(parse(&["play", "--once"]).unwrap().max_frames, Some(1));
+
+        let args = parse(&["render", "out.wav", "--minutes", "2"]).unwrap();
+        let path = PathBuf::from("out.wav");
+        let render = |looped| Command::Render {
+            path: path.clone(),
+            looped,
+        };
+        assert_eq!((args.command, args.minutes), (render(false), 2));
+        assert_eq!(
+            parse(&["render", "out.wav", "--loop"]).unwrap().command,
+            render(true)
+        );
+
+        let args = parse(&["inspect", "--json-status", "--test-tone", "staircase"]).unwrap();
+        assert_eq!(args.command, Command::Inspect);
+        assert!(args.json_status);
+        assert_eq!(args.test_tone, Some(TestPattern::Staircase));
+
+        assert_eq!(
+            parse(&["verify"]).unwrap().command,
+            Command::Verify { recording: None }
+        );
+        let at = "2023-08-20T09:15:00+08:00";
+        assert_eq!(
+            parse(&["verify", "--wav", "in.wav", "--at", at])
+                .unwrap()
+                .command,
+            Command::Verify {
+                recording: Some((PathBuf::from("in.wav"), rfc3339(at).unwrap()))
+            }
+        );
+        let args = parse(&["devices", "--backend", "alsa"]).unwrap();
+        assert_eq!(args.command, Command::Devices);
+        assert_eq!(args.backend.as_deref(), Some("alsa"));
+
+        // each subcommand takes only its own options
+        assert!(parse(&["render"]).is_err());
+        assert!(parse(&["render", "out.wav", "--gain", "2"]).is_err());
+        assert!(parse(&["verify", "--test-tone", "steady"]).is_err());
+        assert!(parse(&["verify", "--wav", "in.wav"]).is_err());
+        assert!(parse(&["devices", "--device", "usb"]).is_err());
+        assert!(parse(&["--dry-run"]).is_err());
+
+        assert!(parse(&["--sweep", "5,0"]).is_err());
+        assert_eq!(parse(&["--sweep", "5,7"]).unwrap().sweep, Some(vec![5, 7]));
+        assert!(parse(&["--once", "--max-frames", "2"]).is_err());
+        assert!(parse(&["--json-status", "--raw"]).is_err());
+        assert!(parse(&["--carrier-divisor", "5", "--sweep", "7"]).is_err());
+        assert!(parse(&["--protocol", "msf"]).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{bpc::DEFAULT_CARRIER, TimeSignal, ZonedDateTime};
+use chrono::Timelike;
+
+/// A fixed sequence of pulses carrying no time, to check that a
+/// receiver picks up the carrier at all before checking what it decodes.
+///
+/// Each pattern repeats every few seconds, aligned on the minute, so a
+/// receiver with a signal indicator shows it steadily once coupled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestPattern {
+    /// The carrier without any pulse, for the signal strength indicator
+    /// alone; what it sends is chosen by
+    /// [`idle`](crate::BPCWaveBuilder::idle), the full carrier by default.
+    Steady,
+    /// 100 ms and 400 ms pulses on alternate seconds, the two extremes of
+    /// BPC.
+    Alternating,
+    /// 100, 200, 300 and 400 ms pulses in turn, every BPC width.
+    Staircase,
+}
+
+impl TimeSignal for TestPattern {
+    fn symbol_width(&self, t: ZonedDateTime) -> Option<u32> {
+        let second = t.second();
+        match self {
+            TestPattern::Steady => None,
+            TestPattern::Alternating if second.is_multiple_of(2) => Some(100),
+            TestPattern::Alternating => Some(400),
+            TestPattern::Staircase => Some(100 * (1 + second % 4)),
+        }
+    }
+
+    /// The same tone as BPC.
+    fn carrier_hz(&self) -> f32 {
+        DEFAULT_CARRIER
+    }
+
+    fn frame_len_secs(&self) -> u32 {
+        match self {
+            TestPattern::Steady => 1,
+            TestPattern::Alternating => 2,
+            TestPattern::Staircase => 4,
+        }
+    }
+
+    /// Describes what is transmitted during the second of `t`, e.g.
+    /// `09:15:07 staircase test pattern (400ms)`.
+    fn describe(&self, t: ZonedDateTime) -> String {
+        let time = t.format("%H:%M:%S");
+        let name = match self {
+            TestPattern::Steady => "steady",
+            TestPattern::Alternating => "alternating",
+            TestPattern::Staircase => "staircase",
+        };
+        match self.symbol_width(t) {
+            Some(width) => format!("{time} {name} test pattern ({width}ms)"),
+            None => format!("{time} {name} test pattern, no pulse"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+
+    #[test]
+    fn pattern_test() {
+        let start = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2023, 8, 20, 9, 15, 0)
+            .unwrap();
+        let widths = |pattern: TestPattern| {
+            (0..8)
+                .map(|s| pattern.symbol_width(start + chrono::Duration::seconds(s)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(widths(TestPattern::Steady), [None; 8]);
+        assert_eq!(
+            widths(TestPattern::Alternating),
+            [100, 400, 100, 400, 100, 400, 100, 400].map(Some)
+        );
+        assert_eq!(
+            widths(TestPattern::Staircase),
+            [100, 200, 300, 400, 100, 200, 300, 400].map(Some)
+        );
+
+        for pattern in [
+            TestPattern::Steady,
+            TestPattern::Alternating,
+            TestPattern::Staircase,
+        ] {
+            // frames line up with the minute
+            assert_eq!(60 % pattern.frame_len_secs(), 0);
+            let len = pattern.frame_len_secs() as i64;
+            let later = start + chrono::Duration::seconds(len * 7);
+            assert_eq!(pattern.symbol_width(later), pattern.symbol_width(start));
+        }
+        assert_eq!(
+            TestPattern::Staircase.describe(start + chrono::Duration::seconds(7)),
+            "09:15:07 staircase test pattern (400ms)"
+        );
+    }
+}
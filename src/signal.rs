@@ -0,0 +1,129 @@
+//! The [`TimeSignal`] trait the protocols implement, and the building blocks
+//! for putting their seconds together by hand.
+
+use crate::ZonedDateTime;
+use chrono::Timelike;
+
+pub use crate::wave::pulse;
+
+/// A time code transmitted by reducing the carrier power at the start of
+/// every second, such as BPC or WWVB.
+///
+/// The reduction starts right on the edge of the second, which is what a
+/// receiver times the second from, and the carrier is back to full power
+/// once the symbol width is over, until the next edge. A signal that is
+/// [`inverted`](TimeSignal::inverted) swaps the two.
+pub trait TimeSignal {
+    /// Width in ms of the power reduction at the start of the second of `t`,
+    /// or `None` when the carrier stays at full power for the whole second.
+    fn symbol_width(&self, t: ZonedDateTime) -> Option<u32>;
+
+    /// Default audio tone in Hz, a subharmonic of the broadcast carrier.
+    fn carrier_hz(&self) -> f32;
+
+    /// Length of a frame in seconds; frames start on multiples of it within
+    /// the minute.
+    fn frame_len_secs(&self) -> u32;
+
+    /// Describes what is transmitted during the second of `t`.
+    fn describe(&self, t: ZonedDateTime) -> String;
+
+    /// Whether the carrier stays at full power for the symbol width and is
+    /// reduced for the rest of the second, as JJY does, instead of the other
+    /// way around.
+    fn inverted(&self) -> bool {
+        false
+    }
+
+    /// The check bit sent during the second of `t`, or `None` when the
+    /// second carries none or the signal does not say.
+    fn check_bit(&self, _t: ZonedDateTime) -> Option<u8> {
+        None
+    }
+
+    /// Returns the start of the frame containing `t`.
+    fn frame_start(&self, t: ZonedDateTime) -> ZonedDateTime {
+        let offset = t.second() % self.frame_len_secs();
+        t.with_nanosecond(0).unwrap() - chrono::Duration::seconds(offset as i64)
+    }
+
+    /// Returns the first frame boundary at or after `t`, where a
+    /// [`frame_aligned`](crate::BPCWaveBuilder::frame_aligned) transmission
+    /// begins.
+    fn next_frame_start(&self, t: ZonedDateTime) -> ZonedDateTime {
+        let start = self.frame_start(t);
+        if start == t {
+            start
+        } else {
+            start + chrono::Duration::seconds(self.frame_len_secs() as i64)
+        }
+    }
+
+    /// Returns second 0 of the minute containing `t`.
+    fn minute_start(&self, t: ZonedDateTime) -> ZonedDateTime {
+        t.with_nanosecond(0).unwrap() - chrono::Duration::seconds(t.second() as i64)
+    }
+
+    /// Returns the first start of a minute at or after `t`, where an
+    /// [`aligned`](crate::BPCWaveBuilder::aligned) transmission begins.
+    fn next_minute_start(&self, t: ZonedDateTime) -> ZonedDateTime {
+        let start = self.minute_start(t);
+        if start == t {
+            start
+        } else {
+            start + chrono::Duration::minutes(1)
+        }
+    }
+}
+
+impl<T: TimeSignal + ?Sized> TimeSignal for Box<T> {
+    fn symbol_width(&self, t: ZonedDateTime) -> Option<u32> {
+        (**self).symbol_width(t)
+    }
+
+    fn carrier_hz(&self) -> f32 {
+        (**self).carrier_hz()
+    }
+
+    fn frame_len_secs(&self) -> u32 {
+        (**self).frame_len_secs()
+    }
+
+    fn describe(&self, t: ZonedDateTime) -> String {
+        (**self).describe(t)
+    }
+
+    fn inverted(&self) -> bool {
+        (**self).inverted()
+    }
+
+    fn check_bit(&self, t: ZonedDateTime) -> Option<u8> {
+        (**self).check_bit(t)
+    }
+}
+
+impl<T: TimeSignal + ?Sized> TimeSignal for &T {
+    fn symbol_width(&self, t: ZonedDateTime) -> Option<u32> {
+        (**self).symbol_width(t)
+    }
+
+    fn carrier_hz(&self) -> f32 {
+        (**self).carrier_hz()
+    }
+
+    fn frame_len_secs(&self) -> u32 {
+        (**self).frame_len_secs()
+    }
+
+    fn describe(&self, t: ZonedDateTime) -> String {
+        (**self).describe(t)
+    }
+
+    fn inverted(&self) -> bool {
+        (**self).inverted()
+    }
+
+    fn check_bit(&self, t: ZonedDateTime) -> Option<u8> {
+        (**self).check_bit(t)
+    }
+}
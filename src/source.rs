@@ -0,0 +1,214 @@
+use crate::{
+    wave::{BPCWaveBuilder, BuildError},
+    Clock, SignalSamples, SignalWave, TimeSignal,
+};
+use chrono::FixedOffset;
+use rodio::Source;
+use std::ops::{Deref, DerefMut};
+
+/// [`SignalSamples`] as a rodio source.
+///
+/// ```
+/// use bpcsync::{BPCWave, MockClock, Waveform};
+/// use chrono::{FixedOffset, TimeZone};
+/// use rodio::Source;
+///
+/// let t = FixedOffset::east_opt(8 * 3600)
+///     .unwrap()
+///     .with_ymd_and_hms(2023, 8, 20, 9, 15, 0)
+///     .unwrap();
+/// let wave = BPCWave::builder()
+///     .carrier(68500. / 7.)
+///     .sample_rate(48000)
+///     .gain(0.5)
+///     .waveform(Waveform::Square)
+///     .clock(MockClock::new(t))
+///     .build()
+///     .unwrap();
+/// assert_eq!((wave.sample_rate(), wave.channels()), (48000, 1));
+/// // hand it to a `rodio::Sink`, or pull the samples directly
+/// let samples: Vec<f32> = wave.take(4800).collect();
+/// assert!(samples.iter().all(|s| s.abs() <= 0.5));
+/// ```
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Default)]
+pub struct BPCWave(SignalSamples);
+
+impl BPCWave {
+    pub fn new() -> Self {
+        Self(SignalSamples::new())
+    }
+
+    pub fn builder() -> BPCWaveBuilder {
+        BPCWaveBuilder::default()
+    }
+
+    /// See [`SignalSamples::with_carrier`].
+    pub fn with_carrier(freq_hz: f32) -> Result<Self, BuildError> {
+        SignalSamples::with_carrier(freq_hz).map(Self)
+    }
+
+    /// See [`SignalSamples::with_sample_rate`].
+    pub fn with_sample_rate(sr: u32) -> Result<Self, BuildError> {
+        SignalSamples::with_sample_rate(sr).map(Self)
+    }
+
+    /// See [`SignalSamples::with_gain`].
+    pub fn with_gain(self, gain: f32) -> Self {
+        Self(self.0.with_gain(gain))
+    }
+
+    /// See [`SignalSamples::with_timezone`].
+    pub fn with_timezone(self, offset: FixedOffset) -> Self {
+        Self(self.0.with_timezone(offset))
+    }
+
+    /// See [`SignalSamples::with_clock`].
+    pub fn with_clock(self, clock: impl Clock + Send + 'static) -> Self {
+        Self(self.0.with_clock(clock))
+    }
+
+    pub fn into_inner(self) -> SignalSamples {
+        self.0
+    }
+}
+
+impl BPCWaveBuilder {
+    /// Validates the configuration and starts the wave.
+    pub fn build(self) -> Result<BPCWave, BuildError> {
+        self.build_samples().map(BPCWave)
+    }
+}
+
+impl From<SignalSamples> for BPCWave {
+    fn from(samples: SignalSamples) -> Self {
+        Self(samples)
+    }
+}
+
+impl Deref for BPCWave {
+    type Target = SignalSamples;
+
+    fn deref(&self) -> &SignalSamples {
+        &self.0
+    }
+}
+
+impl DerefMut for BPCWave {
+    fn deref_mut(&mut self) -> &mut SignalSamples {
+        &mut self.0
+    }
+}
+
+impl Iterator for BPCWave {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        self.0.next()
+    }
+}
+
+impl Source for BPCWave {
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.0.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.0.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl<T: TimeSignal> Source for SignalWave<T> {
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        SignalWave::channels(self)
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        SignalWave::sample_rate(self)
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{MockClock, Waveform};
+    use chrono::TimeZone;
+    use std::time::Duration;
+
+    #[test]
+    fn source_test() {
+        let wave = BPCWave::with_sample_rate(48000).unwrap().with_gain(0.3);
+        assert_eq!(Source::sample_rate(&wave), 48000);
+        assert_eq!(Source::channels(&wave), 1);
+        assert_eq!(wave.carrier(), 13700.);
+        assert!(wave.take(1000).all(|s| s.abs() <= 0.3));
+
+        let samples = BPCWave::builder().gain(0.5).build().unwrap().into_inner();
+        assert!(samples.take(1000).all(|s| s.abs() <= 0.5));
+
+        let stereo = BPCWave::builder().channels(2).build().unwrap();
+        assert_eq!(Source::channels(&stereo), 2);
+    }
+
+    #[test]
+    fn range_test() {
+        let builder = || {
+            let t = FixedOffset::east_opt(8 * 3600)
+                .unwrap()
+                .with_ymd_and_hms(2023, 8, 20, 9, 15, 7)
+                .unwrap();
+            BPCWave::builder().clock(MockClock::new(t))
+        };
+        let mut waves = [
+            builder().build(),
+            builder().gain(4.).build(),
+            builder().gain(2.).modulation_depth(0.5).build(),
+            builder()
+                .waveform(Waveform::Square)
+                .ramp(Duration::ZERO)
+                .build(),
+            builder().channels(3).channel_phase(90.).gain(1.5).build(),
+            builder().carrier(20000.).gain(-3.).build(),
+        ]
+        .map(Result::unwrap);
+        // before, through and after the first update of each
+        let mut published = [false; 6];
+        let mut after = 0;
+        while after < 5000 {
+            for (wave, published) in waves.iter_mut().zip(&mut published) {
+                for s in wave.by_ref().take(64) {
+                    assert!(s.is_finite() && (-1. ..=1.).contains(&s), "{s}");
+                }
+                *published |= wave.status().time.is_some();
+            }
+            if published.iter().all(|&p| p) {
+                after += 64;
+            }
+        }
+    }
+}
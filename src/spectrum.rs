@@ -0,0 +1,75 @@
+//! Finds the strongest tone of rendered samples, to check what actually
+//! reaches the output once it is resampled.
+
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::f64::consts::PI;
+
+/// The strongest bin of a spectrum.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Peak {
+    /// Centre frequency of the bin in Hz.
+    pub freq_hz: f32,
+    /// Amplitude of the tone, 1 for a full-scale sine.
+    pub magnitude: f32,
+}
+
+/// Returns the strongest tone of `samples`, a single channel at
+/// `sample_rate`, or `None` for fewer than two samples.
+///
+/// The samples are analyzed under a Hann window, so the resolution is about
+/// `sample_rate / samples.len()`.
+pub fn peak(samples: &[f32], sample_rate: u32) -> Option<Peak> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let n = samples.len();
+    let window = |i: usize| 0.5 - 0.5 * (2. * PI * i as f64 / n as f64).cos();
+    let mut bins: Vec<Complex<f64>> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| Complex::new(*s as f64 * window(i), 0.))
+        .collect();
+    FftPlanner::new().plan_fft_forward(n).process(&mut bins);
+
+    // the dc bin is left out, a pure offset is no tone
+    let (bin, strongest) = bins[1..=n / 2]
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.norm().total_cmp(&b.norm()))?;
+    let gain: f64 = (0..n).map(window).sum();
+    Some(Peak {
+        freq_hz: ((bin + 1) as f64 * sample_rate as f64 / n as f64) as f32,
+        magnitude: (2. * strongest.norm() / gain) as f32,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SignalWave, BPC};
+    use chrono::{FixedOffset, TimeZone};
+
+    #[test]
+    fn peak_test() {
+        let sample_rate = 48000;
+        let sine: Vec<f32> = (0..sample_rate)
+            .map(|i| 0.5 * (2. * PI * 1000. * i as f64 / sample_rate as f64).sin() as f32)
+            .collect();
+        let found = peak(&sine, sample_rate).unwrap();
+        assert!((found.freq_hz - 1000.).abs() < 2., "{found:?}");
+        assert!((found.magnitude - 0.5).abs() < 0.05, "{found:?}");
+
+        // the carrier of a rendered second, through its reduction
+        let start = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2023, 8, 20, 9, 15, 3)
+            .unwrap();
+        let wave: Vec<f32> = SignalWave::new(BPC::new(), sample_rate, start)
+            .take(sample_rate as usize)
+            .collect();
+        let found = peak(&wave, sample_rate).unwrap();
+        assert!((found.freq_hz - 13700.).abs() < 2., "{found:?}");
+
+        assert_eq!(peak(&[0.5], sample_rate), None);
+    }
+}
@@ -0,0 +1,2623 @@
+use crate::{
+    bpc::{cst_offset, BPC},
+    clock::{Clock, SystemClock},
+    core::width_ms,
+    TimeSignal, ZonedDateTime,
+};
+use anyhow::{bail, Result};
+use chrono::{FixedOffset, TimeZone, Timelike, Utc};
+use log::{debug, info, warn};
+use std::{
+    collections::BTreeMap,
+    f32::consts::PI,
+    f64::consts::TAU,
+    fs::OpenOptions,
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, AtomicI64, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Sample rate used unless configured otherwise, and by [`render`].
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+// length of the envelope transitions between the reduced and full carrier
+const DEFAULT_RAMP: Duration = Duration::from_millis(2);
+// length of the fade in of the very first samples of a `SignalSamples`
+const DEFAULT_FADE_IN: Duration = Duration::from_millis(10);
+// length of the fade out of `Monitor::fade_out`
+const FADE_OUT: Duration = Duration::from_millis(20);
+
+/// Shape of the carrier tone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    /// Richer in harmonics, which some clocks pick up better. It is not
+    /// band-limited, so harmonics above the Nyquist frequency alias.
+    Square,
+}
+
+/// What is emitted during a second without a symbol, like the frame marker,
+/// and before an aligned start.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Idle {
+    Silence,
+    /// The full carrier, as BPC itself sends during the frame marker.
+    #[default]
+    FullCarrier,
+    /// The carrier with this share of its amplitude removed, 0 - 1.
+    ReducedCarrier(f32),
+}
+
+impl Idle {
+    // envelope of the carrier while idle
+    fn level(self) -> f32 {
+        match self {
+            Idle::Silence => 0.,
+            Idle::FullCarrier => 1.,
+            Idle::ReducedCarrier(depth) => 1. - depth,
+        }
+    }
+}
+
+// how the second being emitted is modulated
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Gate {
+    // frame marker: the power is not reduced, the carrier lasts the whole second
+    Marker,
+    // the power is reduced for the first n samples of the second
+    Pulse(usize),
+    // the power is full for the first n samples and reduced for the rest
+    High(usize),
+}
+
+// stands for `Gate::Marker` when a gate is stored in an atomic
+const MARKER: usize = usize::MAX;
+// flags a `Gate::High` when a gate is stored in an atomic
+const HIGH: usize = 1 << (usize::BITS - 1);
+
+impl Gate {
+    fn at(signal: &impl TimeSignal, t: ZonedDateTime, sample_rate: u32) -> Self {
+        match signal.symbol_width(t) {
+            Some(width) if signal.inverted() => Gate::High(width_samples(width, sample_rate)),
+            Some(width) => Gate::Pulse(width_samples(width, sample_rate)),
+            None => Gate::Marker,
+        }
+    }
+
+    fn to_bits(self) -> usize {
+        match self {
+            Gate::Marker => MARKER,
+            Gate::Pulse(gap) => gap,
+            Gate::High(len) => len | HIGH,
+        }
+    }
+
+    // the same second with the full and reduced carrier swapped
+    fn inverse(self) -> Self {
+        match self {
+            Gate::Marker => Gate::High(0),
+            Gate::Pulse(gap) => Gate::High(gap),
+            Gate::High(len) => Gate::Pulse(len),
+        }
+    }
+
+    fn from_bits(bits: usize) -> Self {
+        match bits {
+            MARKER => Gate::Marker,
+            len if len & HIGH != 0 => Gate::High(len & !HIGH),
+            gap => Gate::Pulse(gap),
+        }
+    }
+}
+
+// number of samples lasting `width` ms; computed in u64 so that any width of
+// up to a second is exact at every u32 sample rate
+fn width_samples(width: u32, sample_rate: u32) -> usize {
+    (width as u64 * sample_rate as u64 / 1000) as usize
+}
+
+// the carrier, gated by the modulation of the current second
+struct Oscillator {
+    carrier: f32,
+    sample_rate: u32,
+    gain: f32,
+    // share of the carrier amplitude removed while reduced, 1 cuts it
+    depth: f32,
+    waveform: Waveform,
+    idle: Idle,
+    // carrier phase in radians, kept continuous across second boundaries
+    phase: f64,
+    // further carriers summed with the first one, frequency and phase
+    comb: Vec<(f32, f64)>,
+    num_samples: usize,
+    gate: Gate,
+    // raised-cosine transition length in samples, 0 switches instantly
+    ramp: usize,
+    // envelope transition in progress: from, to and samples done
+    env_from: f32,
+    env_to: f32,
+    ramp_pos: usize,
+    // envelope of the last emitted sample
+    level: f32,
+    // length in samples of the one-time fade in from the first sample, and
+    // samples emitted so far, counted up to it
+    fade_in: usize,
+    emitted: usize,
+    // interleaved channels, all but the first shifted by `channel_phase`
+    channels: u16,
+    channel_phase: f64,
+    // fading out or faded out for good, whatever the gate
+    silenced: bool,
+}
+
+impl Oscillator {
+    fn new(carrier: f32, sample_rate: u32) -> Self {
+        Self {
+            carrier,
+            sample_rate,
+            gain: 1.,
+            depth: 1.,
+            waveform: Waveform::Sine,
+            idle: Idle::FullCarrier,
+            phase: 0.,
+            comb: Vec::new(),
+            num_samples: 0,
+            gate: Gate::Marker,
+            ramp: ramp_samples(DEFAULT_RAMP, sample_rate),
+            env_from: 1.,
+            env_to: 1.,
+            ramp_pos: usize::MAX,
+            level: 1.,
+            fade_in: 0,
+            emitted: 0,
+            channels: 1,
+            channel_phase: 0.,
+            silenced: false,
+        }
+    }
+
+    fn set_ramp(&mut self, ramp: Duration) {
+        self.ramp = ramp_samples(ramp, self.sample_rate);
+    }
+
+    // eases the envelope down to silence over `len`, with the same raised
+    // cosine as the gate transitions
+    fn fade_out(&mut self, len: Duration) {
+        self.env_from = self.envelope();
+        self.env_to = 0.;
+        self.ramp_pos = 0;
+        self.set_ramp(len);
+        self.silenced = true;
+    }
+
+    fn faded(&self) -> bool {
+        self.silenced && self.ramp_pos >= self.ramp
+    }
+
+    // back to the phase and envelope it was built with, fade in included,
+    // as if nothing had been emitted yet; a fade out still holds
+    fn restart(&mut self) {
+        self.phase = 0.;
+        for (_, phase) in &mut self.comb {
+            *phase = 0.;
+        }
+        self.env_from = 1.;
+        self.env_to = 1.;
+        self.ramp_pos = usize::MAX;
+        self.level = 1.;
+        self.emitted = 0;
+    }
+
+    fn update(&mut self, signal: &impl TimeSignal, t: ZonedDateTime) {
+        self.set_gate(Gate::at(signal, t, self.sample_rate));
+    }
+
+    // starts a new second
+    fn set_gate(&mut self, gate: Gate) {
+        self.gate = gate;
+        self.num_samples = 0;
+    }
+
+    fn phase_step(&self) -> f64 {
+        TAU * self.carrier as f64 / self.sample_rate as f64
+    }
+
+    // the last emitted sample with the carrier shifted by `phase` radians
+    fn sample(&self, phase: f64) -> f32 {
+        let tone = |carrier_phase: f64| match self.waveform {
+            Waveform::Sine => (carrier_phase + phase).sin(),
+            Waveform::Square => (carrier_phase + phase).sin().signum(),
+        };
+        let sum: f64 = tone(self.phase) + self.comb.iter().map(|(_, p)| tone(*p)).sum::<f64>();
+        // scaled so that the tones can never add up past a single one
+        let tone = sum / (1 + self.comb.len()) as f64;
+        (tone as f32 * self.gain * self.level).clamp(-1., 1.)
+    }
+
+    // worst case of the samples before they are clamped: each tone peaks at
+    // 1, sine or square, the comb is scaled down by its number of tones and
+    // the envelope never goes past the full carrier, so only the gain is
+    // left
+    fn peak(&self) -> f32 {
+        self.gain.abs()
+    }
+
+    fn envelope(&self) -> f32 {
+        if self.ramp_pos >= self.ramp {
+            return self.env_to;
+        }
+        let x = self.ramp_pos as f32 / self.ramp as f32;
+        let eased = 0.5 - 0.5 * (PI * x).cos();
+        self.env_from + (self.env_to - self.env_from) * eased
+    }
+
+    // raised cosine from silence over the first `fade_in` samples, which
+    // nothing else eases in since the envelope starts wherever the gate is
+    fn fade_in_gain(&self) -> f32 {
+        if self.emitted >= self.fade_in {
+            return 1.;
+        }
+        let x = self.emitted as f32 / self.fade_in as f32;
+        0.5 - 0.5 * (PI * x).cos()
+    }
+}
+
+fn ramp_samples(ramp: Duration, sample_rate: u32) -> usize {
+    (ramp.as_secs_f64() * sample_rate as f64).round() as usize
+}
+
+impl Iterator for Oscillator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let carrier = match self.gate {
+            Gate::Marker => None,
+            Gate::Pulse(gap) => Some(self.num_samples >= gap),
+            Gate::High(len) => Some(self.num_samples < len),
+        };
+        self.num_samples += 1;
+        self.phase = (self.phase + self.phase_step()) % TAU;
+        for (carrier, phase) in &mut self.comb {
+            *phase = (*phase + TAU * *carrier as f64 / self.sample_rate as f64) % TAU;
+        }
+
+        let target = match (self.silenced, carrier) {
+            (true, _) => 0.,
+            (false, None) => self.idle.level(),
+            (false, Some(true)) => 1.,
+            (false, Some(false)) => 1. - self.depth,
+        };
+        if target != self.env_to {
+            // ease from wherever the envelope is, even mid-transition
+            self.env_from = self.envelope();
+            self.env_to = target;
+            self.ramp_pos = 0;
+        }
+        self.level = self.envelope() * self.fade_in_gain();
+        self.ramp_pos = self.ramp_pos.saturating_add(1);
+        self.emitted = (self.emitted + 1).min(self.fade_in);
+
+        Some(self.sample(0.))
+    }
+}
+
+/// The waveform of a time signal starting at a fixed time, without a clock or
+/// a background thread.
+///
+/// Every `sample_rate` samples it moves on to the next second, so the output
+/// is fully determined by `start` and the sample index.
+pub struct SignalWave<T: TimeSignal> {
+    signal: T,
+    osc: Oscillator,
+    // second currently emitted
+    time: ZonedDateTime,
+}
+
+impl<T: TimeSignal> SignalWave<T> {
+    /// Creates a wave emitting `signal` on its default tone, from the second
+    /// of `start` on.
+    pub fn new(signal: T, sample_rate: u32, start: ZonedDateTime) -> Self {
+        let mut osc = Oscillator::new(signal.carrier_hz(), sample_rate);
+        let time = start.with_nanosecond(0).unwrap();
+        osc.update(&signal, time);
+        Self { signal, osc, time }
+    }
+
+    /// Eases every transition between the reduced and full carrier over
+    /// `ramp` instead of the default 2 ms; zero switches instantly.
+    pub fn with_ramp(mut self, ramp: Duration) -> Self {
+        self.osc.set_ramp(ramp);
+        self
+    }
+
+    /// Emits a `freq_hz` tone instead of the default one of the signal. It
+    /// should stay below the Nyquist frequency to avoid aliasing.
+    pub fn with_carrier(mut self, freq_hz: f32) -> Self {
+        self.osc.carrier = freq_hz;
+        self
+    }
+
+    /// Starts over from the second of `start`, with the carrier phase and
+    /// envelope of a new wave, so that the next samples are those of
+    /// [`SignalWave::new`] at `start`.
+    pub fn reset(&mut self, start: ZonedDateTime) {
+        self.time = start.with_nanosecond(0).unwrap();
+        self.osc.restart();
+        self.osc.update(&self.signal, self.time);
+    }
+
+    /// Time of the second currently emitted.
+    pub fn time(&self) -> ZonedDateTime {
+        self.time
+    }
+
+    pub fn channels(&self) -> u16 {
+        1
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.osc.sample_rate
+    }
+}
+
+impl<T: TimeSignal> Iterator for SignalWave<T> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.osc.num_samples >= self.osc.sample_rate as usize {
+            self.time += chrono::Duration::seconds(1);
+            self.osc.update(&self.signal, self.time);
+        }
+        self.osc.next()
+    }
+}
+
+/// Renders the waveform of `signal` at 44100 Hz, without a clock.
+///
+/// `start` is moved back to the boundary of its frame and `duration` is
+/// rounded up to whole frames, so the output never cuts a frame in half.
+pub fn render<T: TimeSignal>(
+    signal: T,
+    start: ZonedDateTime,
+    duration: Duration,
+) -> impl Iterator<Item = f32> {
+    let start = signal.frame_start(start);
+    let frame_len = signal.frame_len_secs() as f64;
+    let frames = (duration.as_secs_f64() / frame_len).ceil().max(1.);
+    let seconds = (frames * frame_len) as usize;
+    SignalWave::new(signal, DEFAULT_SAMPLE_RATE, start).take(seconds * DEFAULT_SAMPLE_RATE as usize)
+}
+
+/// Renders a single BPC second carrying `code`, `0b00` to `0b11`: the
+/// reduced carrier for its width, then the full `carrier` Hz tone up to the
+/// end of the second, `sample_rate` samples in all. The reduction eases in
+/// from the full carrier, as it would after a frame marker.
+///
+/// No time is involved, so seconds can be put together into any frame, or
+/// a single symbol tested on its own. Each one starts on phase 0 of the
+/// carrier. Also reachable as [`signal::pulse`](crate::signal::pulse).
+///
+/// Returns a `Result` rather than the bare samples: `Err` for a code above
+/// `0b11`, or a carrier the sample rate cannot carry.
+pub fn pulse(code: u8, sample_rate: u32, carrier: f32) -> Result<Vec<f32>> {
+    let Some(width) = width_ms(code) else {
+        bail!("invalid code {code:#04b}, expected 0b00 to 0b11");
+    };
+    check_carrier(carrier, sample_rate)?;
+    let mut osc = Oscillator::new(carrier, sample_rate);
+    osc.set_gate(Gate::Pulse(width_samples(width, sample_rate)));
+    Ok(osc.take(sample_rate as usize).collect())
+}
+
+/// Renders the waveform of `signal` into a 16-bit PCM WAV file, see
+/// [`render`].
+pub fn render_wav(
+    signal: &impl TimeSignal,
+    path: &Path,
+    start: ZonedDateTime,
+    duration: Duration,
+) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: DEFAULT_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for sample in render(signal, start, duration) {
+        writer.write_sample((sample * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Renders the minute containing `start`, from its second 0, into a 16-bit
+/// PCM WAV file that loops without a click.
+///
+/// The tone is rounded to a multiple of 1/60 Hz so that the minute holds
+/// whole carrier cycles and its last sample leads back into the first one,
+/// and a `smpl` chunk marks the whole file as a loop for players that
+/// honour it. A receiver syncing to the loop sees the same minute over and
+/// over, so it only sets the right time once, on the first pass.
+pub fn render_loop_wav(signal: &impl TimeSignal, path: &Path, start: ZonedDateTime) -> Result<()> {
+    let minute = signal.minute_start(start);
+    let carrier = loop_carrier(signal.carrier_hz());
+    let len = 60 * DEFAULT_SAMPLE_RATE;
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: DEFAULT_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    let wave = SignalWave::new(signal, DEFAULT_SAMPLE_RATE, minute).with_carrier(carrier);
+    for sample in wave.take(len as usize) {
+        writer.write_sample((sample * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    append_loop_chunk(path, DEFAULT_SAMPLE_RATE, len)
+}
+
+// nearest tone completing a whole number of cycles in a minute
+fn loop_carrier(carrier: f32) -> f32 {
+    ((carrier as f64 * 60.).round() / 60.) as f32
+}
+
+// appends a `smpl` chunk looping over all `len` samples forever and fixes up
+// the RIFF size, which hound has no way to write
+fn append_loop_chunk(path: &Path, sample_rate: u32, len: u32) -> Result<()> {
+    let fields = [
+        0,                           // manufacturer
+        0,                           // product
+        1_000_000_000 / sample_rate, // sample period in ns
+        60,                          // midi unity note
+        0,                           // midi pitch fraction
+        0,                           // smpte format
+        0,                           // smpte offset
+        1,                           // sample loops
+        0,                           // sampler data
+        0,                           // cue point id
+        0,                           // forward loop
+        0,                           // first sample
+        len - 1,                     // last sample
+        0,                           // fraction
+        0,                           // play count, 0 for forever
+    ];
+    let mut chunk = b"smpl".to_vec();
+    chunk.extend((fields.len() as u32 * 4).to_le_bytes());
+    chunk.extend(fields.iter().flat_map(|f| f.to_le_bytes()));
+
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let riff_len = file.seek(SeekFrom::End(0))? + chunk.len() as u64 - 8;
+    file.write_all(&chunk)?;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&u32::try_from(riff_len)?.to_le_bytes())?;
+    Ok(())
+}
+
+// state published by the update thread and read by the audio callback
+struct Shared {
+    // bumped once per published second, after filling `slot(generation)`
+    generation: AtomicU64,
+    slots: [Slot; 2],
+    // samples pulled beyond the nominal rate, summed over the seconds
+    drift: AtomicI64,
+    // frames published whole
+    frames: AtomicU64,
+    // asked to fade out, and done fading
+    fading: AtomicBool,
+    faded: AtomicBool,
+    // asked to start over on the clock, see `Monitor::reset`
+    resetting: AtomicBool,
+    // ms after `created` the update thread last woke up, and the output
+    // last went through a second of samples
+    created: Instant,
+    heartbeat_ms: AtomicU64,
+    pulled_ms: AtomicU64,
+}
+
+// one published second; the update thread fills the slot of the next
+// generation while the current one is read, so no reader ever sees a second
+// half written, as long as it takes less than a second to read one
+struct Slot {
+    gate: AtomicUsize,
+    // what was published, for `SignalSamples::status`
+    second: AtomicU32,
+    // symbol width in ms, `NO_WIDTH` for none
+    width: AtomicU32,
+    timestamp_ms: AtomicI64,
+    utc_offset: AtomicI32,
+    // how late the update thread woke up for the second
+    wake_error_us: AtomicI64,
+    // ms after `Shared::created` the second was published
+    published_ms: AtomicU64,
+}
+
+// stands for a second without a symbol width in `Slot::width`
+const NO_WIDTH: u32 = u32::MAX;
+
+impl Slot {
+    fn new() -> Self {
+        Self {
+            gate: AtomicUsize::new(Gate::Marker.to_bits()),
+            second: AtomicU32::new(0),
+            width: AtomicU32::new(NO_WIDTH),
+            timestamp_ms: AtomicI64::new(0),
+            utc_offset: AtomicI32::new(0),
+            wake_error_us: AtomicI64::new(0),
+            published_ms: AtomicU64::new(0),
+        }
+    }
+
+    fn time(&self) -> Option<ZonedDateTime> {
+        let offset = FixedOffset::east_opt(self.utc_offset.load(Ordering::SeqCst))?;
+        let t = Utc.timestamp_millis_opt(self.timestamp_ms.load(Ordering::SeqCst));
+        Some(t.single()?.with_timezone(&offset))
+    }
+}
+
+impl Shared {
+    fn new() -> Self {
+        Self {
+            generation: AtomicU64::new(0),
+            slots: [Slot::new(), Slot::new()],
+            drift: AtomicI64::new(0),
+            frames: AtomicU64::new(0),
+            fading: AtomicBool::new(false),
+            faded: AtomicBool::new(false),
+            resetting: AtomicBool::new(false),
+            created: Instant::now(),
+            heartbeat_ms: AtomicU64::new(0),
+            pulled_ms: AtomicU64::new(0),
+        }
+    }
+
+    fn beat(&self) {
+        let ms = self.created.elapsed().as_millis() as u64;
+        self.heartbeat_ms.store(ms, Ordering::SeqCst);
+    }
+
+    fn pulled(&self) {
+        let ms = self.created.elapsed().as_millis() as u64;
+        self.pulled_ms.store(ms, Ordering::SeqCst);
+    }
+
+    fn slot(&self, generation: u64) -> &Slot {
+        &self.slots[generation as usize % 2]
+    }
+
+    // retries in the unlikely case that the slot was reused while being read
+    fn status(&self) -> TransmitStatus {
+        loop {
+            let generation = self.generation.load(Ordering::SeqCst);
+            let slot = self.slot(generation);
+            let status = TransmitStatus {
+                second: slot.second.load(Ordering::SeqCst),
+                width: Some(slot.width.load(Ordering::SeqCst)).filter(|&w| w != NO_WIDTH),
+                time: slot.time().filter(|_| generation > 0),
+                drift: self.drift.load(Ordering::SeqCst),
+                frames: self.frames.load(Ordering::SeqCst),
+                wake_error_us: slot.wake_error_us.load(Ordering::SeqCst),
+            };
+            if self.generation.load(Ordering::SeqCst) <= generation + 1 {
+                return status;
+            }
+        }
+    }
+}
+
+/// What a [`SignalSamples`] is transmitting, as last published by its
+/// background thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransmitStatus {
+    /// Index of the second within the frame.
+    pub second: u32,
+    /// Symbol width in ms of the second, as [`TimeSignal::symbol_width`].
+    pub width: Option<u32>,
+    /// Time the second was encoded from, `None` until the first second is
+    /// published.
+    pub time: Option<ZonedDateTime>,
+    /// Samples the output pulled beyond its nominal sample rate, summed over
+    /// every second so far; negative when it runs slow. Each second still
+    /// starts on the clock, so this is how much the seconds were stretched
+    /// or cut short to stay on it.
+    pub drift: i64,
+    /// Frames transmitted whole, from their first second to their last one,
+    /// counted as the next frame starts.
+    pub frames: u64,
+    /// Microseconds past the start of the second at which the update thread
+    /// woke up to publish it, negative when it woke up early.
+    pub wake_error_us: i64,
+}
+
+/// Watches a [`SignalSamples`] from another thread, see
+/// [`SignalSamples::monitor`].
+#[derive(Clone)]
+pub struct Monitor(Arc<Shared>);
+
+impl Monitor {
+    /// See [`SignalSamples::status`].
+    pub fn status(&self) -> TransmitStatus {
+        self.0.status()
+    }
+
+    /// Fades the carrier out over 20 ms, after which only silence is
+    /// emitted, so that the output can be stopped without a click.
+    pub fn fade_out(&self) {
+        self.0.fading.store(true, Ordering::SeqCst);
+    }
+
+    /// Restarts the carrier phase and envelope, fade in included, and
+    /// realigns the samples on the second last published, as the samples
+    /// of a new wave would be but without restarting its thread, e.g. when
+    /// they are handed to an output again after a pause.
+    ///
+    /// It is safe to call while the samples are pulled on another thread;
+    /// it takes effect on the next sample pulled.
+    pub fn reset(&self) {
+        self.0.resetting.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the fade out started by [`Monitor::fade_out`] is over.
+    pub fn faded(&self) -> bool {
+        self.0.faded.load(Ordering::SeqCst)
+    }
+
+    /// Time since the update thread last woke up, about once a second while
+    /// it is healthy.
+    pub fn since_last_update(&self) -> Duration {
+        since(&self.0, &self.0.heartbeat_ms)
+    }
+
+    /// Time since the output last went through a second of samples, about a
+    /// second while it plays. It keeps growing once the output stops pulling
+    /// samples, e.g. when its device went away.
+    pub fn since_last_pull(&self) -> Duration {
+        since(&self.0, &self.0.pulled_ms)
+    }
+}
+
+fn since(shared: &Shared, ms: &AtomicU64) -> Duration {
+    let ms = ms.load(Ordering::SeqCst);
+    shared
+        .created
+        .elapsed()
+        .saturating_sub(Duration::from_millis(ms))
+}
+
+// the update thread's state, handed back when the thread stops
+struct Publisher {
+    signal: Box<dyn TimeSignal + Send>,
+    sample_rate: u32,
+    clock: Box<dyn Clock + Send>,
+    // zone the transmitted time is expressed in
+    timezone: FixedOffset,
+    // hold back publishing until the second is a multiple of this, 60 for
+    // the top of the next minute
+    aligned: Option<u32>,
+    // how far ahead of the clock the signal runs, to make up for the output
+    // latency
+    offset: chrono::Duration,
+    // last published second and how many seconds of its frame were published
+    // in a row up to it
+    last: Option<ZonedDateTime>,
+    frame_seconds: u32,
+    // spin through the end of each wait instead of trusting the scheduler
+    precise: bool,
+    // sleep this much past each second
+    margin: Duration,
+    // swap the full and reduced carrier of every second
+    invert: bool,
+    // what went out since the start of the minute
+    summary: Summary,
+    shared: Arc<Shared>,
+}
+
+// widths and check bits published over a minute, to tell at a glance from
+// the log that the frame is not stuck
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    // seconds sent per symbol width, `None` for no reduction
+    widths: BTreeMap<Option<u32>, u32>,
+    check_bits: [u32; 2],
+}
+
+impl Summary {
+    fn add(&mut self, width: Option<u32>, check_bit: Option<u8>) {
+        *self.widths.entry(width).or_default() += 1;
+        if let Some(bit) = check_bit {
+            self.check_bits[bit as usize & 0b1] += 1;
+        }
+    }
+}
+
+// e.g. `100ms x17, 200ms x12, 300ms x15, 400ms x13, no pulse x3, check bits
+// 0 x4 1 x2`
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut widths: Vec<_> = self.widths.iter().collect();
+        // the marker last, after the widths in order
+        widths.rotate_left(self.widths.contains_key(&None) as usize);
+        for (i, (width, count)) in widths.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match width {
+                Some(w) => write!(f, "{w}ms x{count}")?,
+                None => write!(f, "no pulse x{count}")?,
+            }
+        }
+        let [zeros, ones] = self.check_bits;
+        if zeros + ones > 0 {
+            write!(f, ", check bits 0 x{zeros} 1 x{ones}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Publisher {
+    // publishes the gate of the current second of the configured clock
+    fn tick(&mut self) {
+        self.shared.beat();
+        let now = self.now().with_timezone(&self.timezone);
+        if let Some(period) = self.aligned {
+            if !now.second().is_multiple_of(period) {
+                return;
+            }
+            info!("boundary of {period} seconds reached, start transmitting");
+            self.aligned = None;
+        }
+        let second = now.second() % self.signal.frame_len_secs();
+        self.count_frames(now, second);
+        let mut gate = Gate::at(&self.signal, now, self.sample_rate);
+        if self.invert {
+            gate = gate.inverse();
+        }
+        let width = self.signal.symbol_width(now);
+        if now.second() == 0 && !self.summary.widths.is_empty() {
+            info!("minute up to {}: {}", now.format("%H:%M"), self.summary);
+            self.summary = Summary::default();
+        }
+        self.summary.add(width, self.signal.check_bit(now));
+        let drift = self.shared.drift.load(Ordering::SeqCst);
+        let wake_error = wake_error_us(now);
+        debug!(
+            "{} {gate:?}, drift {drift} samples, woke {wake_error}us late",
+            self.signal.describe(now)
+        );
+        let generation = self.shared.generation.load(Ordering::SeqCst) + 1;
+        let slot = self.shared.slot(generation);
+        slot.gate.store(gate.to_bits(), Ordering::SeqCst);
+        slot.second.store(second, Ordering::SeqCst);
+        slot.width
+            .store(width.unwrap_or(NO_WIDTH), Ordering::SeqCst);
+        slot.timestamp_ms
+            .store(now.timestamp_millis(), Ordering::SeqCst);
+        let offset = now.offset().local_minus_utc();
+        slot.utc_offset.store(offset, Ordering::SeqCst);
+        slot.wake_error_us.store(wake_error, Ordering::SeqCst);
+        let ms = self.shared.created.elapsed().as_millis() as u64;
+        slot.published_ms.store(ms, Ordering::SeqCst);
+        // hands the slot over to the readers
+        self.shared.generation.store(generation, Ordering::SeqCst);
+    }
+
+    // a frame is whole once the next one starts right after all its seconds
+    fn count_frames(&mut self, now: ZonedDateTime, second: u32) {
+        let t = now.with_nanosecond(0).unwrap();
+        if let Some(last) = self.last {
+            // the wait only ever targets the next second, so a step of the
+            // clock is simply followed from the second it lands on
+            let step = t - last;
+            if step < chrono::Duration::zero() || step.to_std().is_ok_and(|s| s > CLOCK_JUMP) {
+                warn!(
+                    "the clock jumped by {}s, following it",
+                    step.num_seconds() - 1
+                );
+            }
+        }
+        let consecutive = self
+            .last
+            .is_some_and(|last| t - last == chrono::Duration::seconds(1));
+        self.last = Some(t);
+        if second == 0 {
+            if consecutive && self.frame_seconds == self.signal.frame_len_secs() {
+                let frames = self.shared.frames.fetch_add(1, Ordering::SeqCst) + 1;
+                info!("{frames} whole frames transmitted");
+            }
+            self.frame_seconds = 1;
+        } else if consecutive && self.frame_seconds > 0 {
+            self.frame_seconds += 1;
+        } else {
+            self.frame_seconds = 0;
+        }
+    }
+
+    fn now(&self) -> ZonedDateTime {
+        self.clock.now() + self.offset
+    }
+
+    fn run(mut self, shutdown: Receiver<()>) -> Self {
+        loop {
+            let now = self.now();
+            let until = until_next_second(now);
+            let next = now + chrono::Duration::from_std(until).unwrap();
+            let delta = until + self.margin;
+            let deadline = Instant::now() + delta;
+            let sleep = match self.precise {
+                true => delta.saturating_sub(PRECISE_SPIN),
+                false => delta,
+            };
+            match shutdown.recv_timeout(sleep) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return self,
+            }
+            while self.precise && Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+            self.wake(next);
+        }
+    }
+
+    // publishes the second starting at `next`, and returns whether it did: a
+    // wake up that is early even with the margin would publish the previous
+    // second again, so it waits for another one instead
+    fn wake(&mut self, next: ZonedDateTime) -> bool {
+        let early = next - self.now();
+        if early > chrono::Duration::zero() {
+            info!(
+                "woke up {}us before the second, waiting again",
+                early.num_microseconds().unwrap_or(i64::MAX)
+            );
+            return false;
+        }
+        self.tick();
+        true
+    }
+}
+
+// how close to the second `precise_timing` stops sleeping and starts spinning
+const PRECISE_SPIN: Duration = Duration::from_millis(2);
+// how long past each second the update thread sleeps, see
+// `BPCWaveBuilder::wake_margin`
+const DEFAULT_WAKE_MARGIN: Duration = Duration::from_micros(500);
+// longest step between published seconds that is a late wake up rather than
+// a step of the clock
+const CLOCK_JUMP: Duration = Duration::from_secs(2);
+
+// never more than a second, whatever the clock does; chrono counts a leap
+// second past 1_000_000us
+fn until_next_second(t: ZonedDateTime) -> Duration {
+    Duration::from_micros(1_000_000 - (t.timestamp_subsec_micros() % 1_000_000) as u64)
+}
+
+// signed distance from `t` to the nearest start of a second
+fn wake_error_us(t: ZonedDateTime) -> i64 {
+    let us = (t.timestamp_subsec_micros() % 1_000_000) as i64;
+    if us >= 500_000 {
+        us - 1_000_000
+    } else {
+        us
+    }
+}
+
+/// A time signal, BPC by default, as an endless stream of mono samples
+/// following the clock.
+///
+/// A background thread follows the clock and publishes the modulation of each
+/// second through atomics, so pulling samples never blocks. It does not
+/// depend on any audio library; [`BPCWave`](crate::BPCWave) wraps it into a
+/// rodio source.
+pub struct SignalSamples {
+    inner: Oscillator,
+    shared: Arc<Shared>,
+    // last generation applied to `inner`
+    generation: u64,
+    // dropping the sender wakes the thread up and makes it exit
+    shutdown: Option<Sender<()>>,
+    thread: Option<JoinHandle<Publisher>>,
+    // the update thread died, only a steady carrier is emitted
+    stalled: bool,
+    // channel of the next sample within the interleaved frame
+    channel: u16,
+}
+
+impl SignalSamples {
+    pub fn new() -> Self {
+        BPCWaveBuilder::default().build_samples().unwrap()
+    }
+
+    pub fn builder() -> BPCWaveBuilder {
+        BPCWaveBuilder::default()
+    }
+
+    /// Creates a wave whose tone is `freq_hz` instead of the default 13700 Hz
+    /// (the 5th subharmonic of the 68.5 kHz BPC carrier).
+    ///
+    /// The frequency must be positive and below the Nyquist frequency.
+    pub fn with_carrier(freq_hz: f32) -> Result<Self, BuildError> {
+        BPCWaveBuilder::default().carrier(freq_hz).build_samples()
+    }
+
+    /// Creates a wave emitted at `sr` Hz instead of the default 44100 Hz, so
+    /// it can match the native rate of the output device.
+    pub fn with_sample_rate(sr: u32) -> Result<Self, BuildError> {
+        BPCWaveBuilder::default().sample_rate(sr).build_samples()
+    }
+
+    fn spawn(inner: Oscillator, mut publisher: Publisher) -> Self {
+        let mut wave = Self {
+            inner,
+            shared: publisher.shared.clone(),
+            generation: 0,
+            shutdown: None,
+            thread: None,
+            stalled: false,
+            channel: 0,
+        };
+        wave.prime(&mut publisher);
+        wave.start(publisher);
+        wave
+    }
+
+    // publishes the second under way before the thread first wakes up, a
+    // second later at worst, and skips the samples already due in it, so
+    // that the first samples pulled carry the right pulse
+    fn prime(&mut self, publisher: &mut Publisher) {
+        publisher.tick();
+        let generation = self.shared.generation.load(Ordering::SeqCst);
+        if generation == self.generation {
+            // waiting for an aligned start
+            return;
+        }
+        self.generation = generation;
+        let slot = self.shared.slot(generation);
+        self.inner
+            .set_gate(Gate::from_bits(slot.gate.load(Ordering::SeqCst)));
+        let ms = slot.timestamp_ms.load(Ordering::SeqCst).rem_euclid(1000);
+        self.inner.num_samples = (ms as u64 * self.inner.sample_rate as u64 / 1000) as usize;
+    }
+
+    fn start(&mut self, publisher: Publisher) {
+        let (shutdown, shutdown_rx) = channel::<()>();
+        self.shutdown = Some(shutdown);
+        self.thread = Some(thread::spawn(move || publisher.run(shutdown_rx)));
+    }
+
+    // stops the update thread and returns its state
+    fn stop(&mut self) -> Option<Publisher> {
+        drop(self.shutdown.take());
+        self.thread.take().and_then(|thread| thread.join().ok())
+    }
+
+    // changes the update thread's configuration by restarting it
+    fn reconfigure(mut self, f: impl FnOnce(&mut Publisher)) -> Self {
+        if let Some(mut publisher) = self.stop() {
+            f(&mut publisher);
+            self.prime(&mut publisher);
+            self.start(publisher);
+        }
+        self
+    }
+
+    /// Scales every emitted sample by `gain`; the result is clamped to
+    /// `[-1.0, 1.0]`.
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.inner.gain = gain;
+        self
+    }
+
+    /// Transmits the time in `offset` instead of China Standard Time.
+    pub fn with_timezone(self, offset: FixedOffset) -> Self {
+        self.reconfigure(|publisher| publisher.timezone = offset)
+    }
+
+    /// Reads the transmitted time from `clock` instead of the system clock.
+    pub fn with_clock(self, clock: impl Clock + Send + 'static) -> Self {
+        self.reconfigure(|publisher| publisher.clock = Box::new(clock))
+    }
+
+    pub fn carrier(&self) -> f32 {
+        self.inner.carrier
+    }
+
+    /// Returns what is being transmitted, cheap enough to poll for a live
+    /// view.
+    pub fn status(&self) -> TransmitStatus {
+        self.shared.status()
+    }
+
+    /// Returns a handle that keeps reporting on these samples once they are
+    /// handed over to an audio output.
+    pub fn monitor(&self) -> Monitor {
+        Monitor(self.shared.clone())
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.inner.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate
+    }
+}
+
+impl Drop for SignalSamples {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Default for SignalSamples {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures and creates a [`SignalSamples`], or with the `rodio` feature a
+/// [`BPCWave`](crate::BPCWave).
+///
+/// ```no_run
+/// let wave = bpcsync::SignalSamples::builder()
+///     .carrier(68500. / 9.)
+///     .sample_rate(48000)
+///     .gain(0.3)
+///     .build_samples()
+///     .unwrap();
+/// ```
+pub struct BPCWaveBuilder {
+    signal: Box<dyn TimeSignal + Send>,
+    carrier: Option<f32>,
+    carriers: Vec<f32>,
+    sample_rate: u32,
+    gain: f32,
+    normalize: bool,
+    depth: f32,
+    waveform: Waveform,
+    idle: Idle,
+    invert: bool,
+    ramp: Duration,
+    fade_in: Duration,
+    channels: u16,
+    channel_phase: f32,
+    timezone: FixedOffset,
+    aligned: bool,
+    frame_aligned: bool,
+    precise_timing: bool,
+    wake_margin: Duration,
+    offset: Duration,
+    clock: Box<dyn Clock + Send>,
+}
+
+impl Default for BPCWaveBuilder {
+    fn default() -> Self {
+        Self {
+            signal: Box::new(BPC::new()),
+            carrier: None,
+            carriers: Vec::new(),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            gain: 1.,
+            normalize: false,
+            depth: 1.,
+            waveform: Waveform::Sine,
+            idle: Idle::FullCarrier,
+            invert: false,
+            ramp: DEFAULT_RAMP,
+            fade_in: DEFAULT_FADE_IN,
+            channels: 1,
+            channel_phase: 0.,
+            timezone: cst_offset(),
+            aligned: false,
+            frame_aligned: false,
+            precise_timing: false,
+            wake_margin: DEFAULT_WAKE_MARGIN,
+            offset: Duration::ZERO,
+            clock: Box::new(SystemClock),
+        }
+    }
+}
+
+impl BPCWaveBuilder {
+    /// Time code to transmit, BPC by default.
+    pub fn signal(mut self, signal: impl TimeSignal + Send + 'static) -> Self {
+        self.signal = Box::new(signal);
+        self
+    }
+
+    /// Tone frequency in Hz, [`TimeSignal::carrier_hz`] of the signal by
+    /// default.
+    pub fn carrier(mut self, freq_hz: f32) -> Self {
+        self.carrier = Some(freq_hz);
+        self
+    }
+
+    /// Sums a tone at each of `freqs_hz` instead of the single
+    /// [`carrier`](Self::carrier), to reach several harmonics of a clock at
+    /// once. The sum is scaled down to the peak of a single tone; an empty
+    /// list keeps the single carrier.
+    pub fn carriers(mut self, freqs_hz: Vec<f32>) -> Self {
+        self.carriers = freqs_hz;
+        self
+    }
+
+    /// Output sample rate in Hz, 44100 Hz by default.
+    pub fn sample_rate(mut self, sr: u32) -> Self {
+        self.sample_rate = sr;
+        self
+    }
+
+    /// Factor applied to every sample, 1.0 by default.
+    ///
+    /// Samples are clamped to `[-1.0, 1.0]`. Each tone peaks at 1, sine or
+    /// square, several [`carriers`](Self::carriers) are scaled down to the
+    /// peak of one and the envelope never exceeds the full carrier, so the
+    /// samples peak at the gain itself; past 1.0 the building warns that
+    /// the carrier clips, unless [`normalize`](Self::normalize) is set.
+    pub fn gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Scales a gain that would clip down to a peak of 1.0 instead of
+    /// warning about it, false by default.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Length of the raised-cosine ramp easing every transition between the
+    /// reduced and full carrier, 2 ms by default; zero switches instantly.
+    ///
+    /// Smoothing the envelope keeps the gating from spreading transients far
+    /// outside the carrier frequency.
+    pub fn ramp(mut self, ramp: Duration) -> Self {
+        self.ramp = ramp;
+        self
+    }
+
+    /// Eases the very first samples in from silence over `fade_in`, 10 ms
+    /// by default, so that starting the output does not pop; zero starts
+    /// right away. It happens once, whatever the later transitions.
+    pub fn fade_in(mut self, fade_in: Duration) -> Self {
+        self.fade_in = fade_in;
+        self
+    }
+
+    /// Share of the carrier amplitude removed while the power is reduced,
+    /// from 0.0 to 1.0. The default 1.0 cuts the carrier entirely, a lower
+    /// one leaves a shallower notch like the real transmitter does.
+    pub fn modulation_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Shape of the carrier tone, a sine by default.
+    pub fn waveform(mut self, waveform: Waveform) -> Self {
+        self.waveform = waveform;
+        self
+    }
+
+    /// What to emit during a second without a symbol and before an aligned
+    /// start, the full carrier by default. An [`invert`](Self::invert)ed
+    /// second without a symbol stays reduced.
+    pub fn idle(mut self, idle: Idle) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Swaps the full and reduced carrier of every second, for receivers
+    /// expecting the opposite polarity: the carrier is full for the symbol
+    /// width and reduced for the rest of the second, and reduced for the
+    /// whole of a second without a symbol. Disabled by default.
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    /// Number of interleaved channels, 1 by default. Every channel carries the
+    /// same signal.
+    pub fn channels(mut self, channels: u16) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Shifts the carrier of every channel but the first by `degrees`, 180
+    /// inverting it. None by default.
+    pub fn channel_phase(mut self, degrees: f32) -> Self {
+        self.channel_phase = degrees;
+        self
+    }
+
+    /// Zone the transmitted time is expressed in, CST by default.
+    pub fn timezone(mut self, offset: FixedOffset) -> Self {
+        self.timezone = offset;
+        self
+    }
+
+    /// Starts transmitting at the top of the next minute, so a receiver sees
+    /// a complete minute from its first frame.
+    ///
+    /// Until then the carrier is emitted steadily, which adds up to 60 seconds
+    /// of startup latency. Disabled by default.
+    pub fn aligned(mut self, aligned: bool) -> Self {
+        self.aligned = aligned;
+        self
+    }
+
+    /// Like [`BPCWaveBuilder::aligned`], but only waits for the start of the
+    /// next frame, which is enough to send exactly whole frames. Disabled by
+    /// default.
+    pub fn frame_aligned(mut self, aligned: bool) -> Self {
+        self.frame_aligned = aligned;
+        self
+    }
+
+    /// Busy-waits through the last 2 ms before each second instead of
+    /// sleeping, so the second starts on time despite scheduler latency, at
+    /// the cost of some CPU. See [`TransmitStatus::wake_error_us`]. Disabled
+    /// by default.
+    pub fn precise_timing(mut self, precise: bool) -> Self {
+        self.precise_timing = precise;
+        self
+    }
+
+    /// Wakes up `margin` past each second to publish it, 500 µs by
+    /// default, so that a thread woken a little early still reads the new
+    /// second; when it does not, the thread logs it and waits again. It
+    /// shows in [`TransmitStatus::wake_error_us`] and must stay under
+    /// 100 ms.
+    pub fn wake_margin(mut self, margin: Duration) -> Self {
+        self.wake_margin = margin;
+        self
+    }
+
+    /// Runs the signal `offset` ahead of the clock, so that with that much
+    /// output latency the edges leave the speaker on time. None by default.
+    pub fn offset(mut self, offset: Duration) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Source of the transmitted time, the system clock by default.
+    pub fn clock(mut self, clock: impl Clock + Send + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Validates the configuration and starts the samples.
+    pub fn build_samples(self) -> Result<SignalSamples, BuildError> {
+        let (inner, publisher) = self.parts()?;
+        Ok(SignalSamples::spawn(inner, publisher))
+    }
+
+    fn parts(self) -> Result<(Oscillator, Publisher), BuildError> {
+        if self.sample_rate == 0 {
+            return Err(BuildError::InvalidSampleRate(self.sample_rate));
+        }
+        let mut carriers = self.carriers.into_iter();
+        let carrier = carriers
+            .next()
+            .or(self.carrier)
+            .unwrap_or_else(|| self.signal.carrier_hz());
+        let comb: Vec<f32> = carriers.collect();
+        for carrier in std::iter::once(&carrier).chain(&comb) {
+            check_carrier(*carrier, self.sample_rate)?;
+        }
+        if !self.gain.is_finite() {
+            return Err(BuildError::InvalidGain(self.gain));
+        }
+        // the shortest reduction of any signal is 100ms
+        if self.ramp >= Duration::from_millis(50) {
+            return Err(BuildError::InvalidRamp(self.ramp));
+        }
+        if self.channels == 0 {
+            return Err(BuildError::NoChannels);
+        }
+        if !(0. ..=1.).contains(&self.depth) {
+            return Err(BuildError::InvalidModulationDepth(self.depth));
+        }
+        let offset = chrono::Duration::from_std(self.offset)
+            .map_err(|_| BuildError::InvalidOffset(self.offset))?;
+        if self.wake_margin >= Duration::from_millis(100) {
+            return Err(BuildError::InvalidWakeMargin(self.wake_margin));
+        }
+        if let Idle::ReducedCarrier(depth) = self.idle {
+            if !(0. ..=1.).contains(&depth) {
+                return Err(BuildError::InvalidIdle(depth));
+            }
+        }
+
+        let mut inner = Oscillator::new(carrier, self.sample_rate);
+        inner.comb = comb.into_iter().map(|carrier| (carrier, 0.)).collect();
+        inner.gain = self.gain;
+        inner.depth = self.depth;
+        inner.waveform = self.waveform;
+        inner.idle = self.idle;
+        inner.set_ramp(self.ramp);
+        inner.fade_in = ramp_samples(self.fade_in, self.sample_rate);
+        inner.channels = self.channels;
+        inner.channel_phase = (self.channel_phase as f64).to_radians();
+        let peak = inner.peak();
+        if peak > 1. && self.normalize {
+            inner.gain /= peak;
+            info!(
+                "gain {} scaled down to {} so that the carrier does not clip",
+                self.gain, inner.gain
+            );
+        } else if peak > 1. {
+            warn!("the carrier peaks at {peak} and is clipped to 1, distorting it");
+        }
+        let aligned = match (self.aligned, self.frame_aligned) {
+            (true, _) => Some(60),
+            (false, true) => Some(self.signal.frame_len_secs()),
+            (false, false) => None,
+        };
+        let publisher = Publisher {
+            signal: self.signal,
+            sample_rate: self.sample_rate,
+            clock: self.clock,
+            timezone: self.timezone,
+            aligned,
+            offset,
+            last: None,
+            frame_seconds: 0,
+            precise: self.precise_timing,
+            margin: self.wake_margin,
+            invert: self.invert,
+            summary: Summary::default(),
+            shared: Arc::new(Shared::new()),
+        };
+        Ok((inner, publisher))
+    }
+}
+
+/// Why a [`BPCWaveBuilder`] rejected its configuration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuildError {
+    /// The sample rate is 0.
+    InvalidSampleRate(u32),
+    /// The carrier is not positive, or would alias at the sample rate.
+    InvalidCarrier {
+        carrier: f32,
+        sample_rate: u32,
+    },
+    /// The gain is infinite or NaN.
+    InvalidGain(f32),
+    /// The ramp is 50 ms or longer.
+    InvalidRamp(Duration),
+    NoChannels,
+    /// The modulation depth is not between 0 and 1.
+    InvalidModulationDepth(f32),
+    /// The reduction of [`Idle::ReducedCarrier`] is not between 0 and 1.
+    InvalidIdle(f32),
+    /// The offset is too large to add to a time.
+    InvalidOffset(Duration),
+    /// The wake margin is 100 ms or longer.
+    InvalidWakeMargin(Duration),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            BuildError::InvalidSampleRate(sr) => write!(f, "sample rate {sr} must be positive"),
+            BuildError::InvalidCarrier {
+                carrier,
+                sample_rate,
+            } if carrier > 0. => write!(
+                f,
+                "carrier frequency {carrier} Hz would alias at a {sample_rate} Hz sample rate, \
+                 it must be below {} Hz",
+                sample_rate as f32 / 2.
+            ),
+            BuildError::InvalidCarrier { carrier, .. } => {
+                write!(f, "carrier frequency {carrier} Hz must be positive")
+            }
+            BuildError::InvalidGain(gain) => write!(f, "gain {gain} is not finite"),
+            BuildError::InvalidRamp(ramp) => write!(f, "ramp {ramp:?} must be shorter than 50ms"),
+            BuildError::NoChannels => write!(f, "there must be at least one channel"),
+            BuildError::InvalidModulationDepth(depth) => {
+                write!(f, "modulation depth {depth} must be between 0 and 1")
+            }
+            BuildError::InvalidIdle(depth) => {
+                write!(f, "idle reduction {depth} must be between 0 and 1")
+            }
+            BuildError::InvalidOffset(offset) => write!(f, "offset {offset:?} is too large"),
+            BuildError::InvalidWakeMargin(margin) => {
+                write!(f, "wake margin {margin:?} must be shorter than 100ms")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+// rejects a carrier that would alias and warns when it nears the nyquist
+// frequency, where few sample points per cycle make the tone ragged
+fn check_carrier(carrier: f32, sample_rate: u32) -> Result<(), BuildError> {
+    let nyquist = sample_rate as f32 / 2.;
+    if !(carrier > 0. && carrier < nyquist) {
+        return Err(BuildError::InvalidCarrier {
+            carrier,
+            sample_rate,
+        });
+    }
+    if carrier > 0.4 * sample_rate as f32 {
+        warn!("carrier frequency {carrier} Hz is close to the {nyquist} Hz nyquist frequency");
+    }
+    Ok(())
+}
+
+impl Iterator for SignalSamples {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        // the other channels repeat the frame of the first one
+        if self.channel > 0 {
+            self.channel = (self.channel + 1) % self.inner.channels;
+            return Some(self.inner.sample(self.inner.channel_phase));
+        }
+        self.channel = 1 % self.inner.channels;
+        self.next_frame()
+    }
+}
+
+impl SignalSamples {
+    fn next_frame(&mut self) -> Option<f32> {
+        if !self.inner.silenced && self.shared.fading.load(Ordering::SeqCst) {
+            self.inner.fade_out(FADE_OUT);
+        }
+        if self.shared.resetting.swap(false, Ordering::SeqCst) {
+            self.realign();
+        }
+        let sample = self.gated_frame();
+        if self.inner.faded() && !self.shared.faded.load(Ordering::SeqCst) {
+            self.shared.faded.store(true, Ordering::SeqCst);
+        }
+        sample
+    }
+
+    // moves to where the second last published is by now, on a fresh
+    // carrier; the seconds missed while paused count for no drift
+    fn realign(&mut self) {
+        self.inner.restart();
+        let generation = self.shared.generation.load(Ordering::SeqCst);
+        if generation == 0 || self.stalled {
+            return;
+        }
+        self.generation = generation;
+        let slot = self.shared.slot(generation);
+        self.inner
+            .set_gate(Gate::from_bits(slot.gate.load(Ordering::SeqCst)));
+        let late = slot.wake_error_us.load(Ordering::SeqCst).max(0) as u64;
+        let into = since(&self.shared, &slot.published_ms) + Duration::from_micros(late);
+        let ms = into.as_millis().min(999) as u32;
+        self.inner.num_samples = width_samples(ms, self.inner.sample_rate);
+    }
+
+    fn gated_frame(&mut self) -> Option<f32> {
+        if self.stalled {
+            return self.inner.next();
+        }
+
+        // a thread that panicked stops publishing
+        let late = self.inner.num_samples > self.inner.sample_rate as usize;
+        if late && self.thread.as_ref().is_some_and(|t| t.is_finished()) {
+            warn!("the update thread died, transmitting a steady carrier from now on");
+            self.stalled = true;
+            self.inner.set_gate(Gate::Marker);
+            return self.inner.next();
+        }
+
+        let generation = self.shared.generation.load(Ordering::SeqCst);
+        if generation != self.generation {
+            // only a second following right after another one was whole
+            if self.generation > 0 && generation == self.generation + 1 {
+                let delta = self.inner.num_samples as i64 - self.inner.sample_rate as i64;
+                self.shared.drift.fetch_add(delta, Ordering::SeqCst);
+            }
+            self.generation = generation;
+            let gate = self.shared.slot(generation).gate.load(Ordering::SeqCst);
+            self.inner.set_gate(Gate::from_bits(gate));
+        }
+        // once per second of samples, published or not
+        if self
+            .inner
+            .num_samples
+            .is_multiple_of(self.inner.sample_rate as usize)
+        {
+            self.shared.pulled();
+        }
+        self.inner.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bpc::DEFAULT_CARRIER;
+    use crate::clock::{MockClock, SimulatedClock};
+    use crate::TestPattern;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> ZonedDateTime {
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(y, mo, d, h, mi, s)
+            .unwrap()
+    }
+
+    // samples without an update thread, for tests that publish by hand
+    fn samples(inner: Oscillator, publisher: &Publisher) -> SignalSamples {
+        SignalSamples {
+            inner,
+            shared: publisher.shared.clone(),
+            generation: 0,
+            shutdown: None,
+            thread: None,
+            stalled: false,
+            channel: 0,
+        }
+    }
+
+    fn published(shared: &Shared) -> Gate {
+        let generation = shared.generation.load(Ordering::SeqCst);
+        Gate::from_bits(shared.slot(generation).gate.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn carrier_test() {
+        assert_eq!(SignalSamples::new().carrier(), 13700.);
+        assert_eq!(
+            SignalSamples::with_carrier(68500. / 9.).unwrap().carrier(),
+            68500. / 9.
+        );
+        assert!(SignalSamples::with_carrier(DEFAULT_SAMPLE_RATE as f32 / 2.).is_err());
+        assert!(SignalSamples::with_carrier(0.).is_err());
+    }
+
+    #[test]
+    fn sample_rate_test() {
+        let wave = SignalSamples::with_sample_rate(48000).unwrap();
+        assert_eq!(wave.sample_rate(), 48000);
+        assert!(SignalSamples::with_sample_rate(22050).is_err());
+        assert!(SignalSamples::with_sample_rate(0).is_err());
+
+        let mut inner = Oscillator::new(DEFAULT_CARRIER, 48000);
+        inner.update(&BPC::new(), at(2023, 8, 20, 9, 15, 2));
+        assert_eq!(inner.gate, Gate::Pulse(4800));
+    }
+
+    #[test]
+    fn width_samples_test() {
+        assert_eq!(width_samples(400, 192000), 76800);
+        assert_eq!(width_samples(1000, u32::MAX), u32::MAX as usize);
+
+        let mut inner = Oscillator::new(DEFAULT_CARRIER, 192000);
+        // minute low of 15 is 0b11, 400ms
+        inner.update(&BPC::new(), at(2023, 8, 20, 9, 15, 7));
+        assert_eq!(inner.gate, Gate::Pulse(76800));
+    }
+
+    #[test]
+    fn gain_test() {
+        let mut wave = SignalSamples::new().with_gain(0.3);
+        assert!(wave.by_ref().take(1000).all(|s| s.abs() <= 0.3));
+        wave.inner.gain = 4.;
+        assert!(wave.take(1000).all(|s| s.abs() <= 1.));
+    }
+
+    #[test]
+    fn clock_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 2));
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+
+        // reserved, 100ms
+        publisher.tick();
+        assert_eq!(published(&publisher.shared), Gate::Pulse(4410));
+
+        // hour high of 9 o'clock is 0b10, 300ms
+        clock.advance(chrono::Duration::seconds(1));
+        publisher.tick();
+        assert_eq!(published(&publisher.shared), Gate::Pulse(13230));
+    }
+
+    #[test]
+    fn aligned_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 58));
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .aligned(true)
+            .parts()
+            .unwrap();
+
+        // steady carrier until the minute starts
+        publisher.tick();
+        assert_eq!(publisher.shared.generation.load(Ordering::SeqCst), 0);
+        assert_eq!(published(&publisher.shared), Gate::Marker);
+
+        clock.advance(chrono::Duration::seconds(2));
+        publisher.tick();
+        assert_eq!(publisher.shared.generation.load(Ordering::SeqCst), 1);
+
+        // reserved, 100ms
+        clock.advance(chrono::Duration::seconds(2));
+        publisher.tick();
+        assert_eq!(published(&publisher.shared), Gate::Pulse(4410));
+
+        // any frame boundary will do
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 38));
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .frame_aligned(true)
+            .parts()
+            .unwrap();
+        publisher.tick();
+        assert_eq!(publisher.shared.generation.load(Ordering::SeqCst), 0);
+        clock.advance(chrono::Duration::seconds(2));
+        publisher.tick();
+        assert_eq!(publisher.shared.status().second, 0);
+        assert_eq!(publisher.shared.generation.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn offset_test() {
+        let t = at(2023, 8, 20, 9, 15, 2) + chrono::Duration::milliseconds(970);
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(MockClock::new(t))
+            .offset(Duration::from_millis(30))
+            .parts()
+            .unwrap();
+        // already the hour high of 9 o'clock, 300ms
+        publisher.tick();
+        assert_eq!(published(&publisher.shared), Gate::Pulse(13230));
+    }
+
+    #[test]
+    fn status_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 22));
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+        let status = publisher.shared.status();
+        assert_eq!(status.time, None);
+
+        // reserved, 100ms
+        publisher.tick();
+        let status = publisher.shared.status();
+        assert_eq!(status.second, 2);
+        assert_eq!(status.width, Some(100));
+        assert_eq!(status.time, Some(at(2023, 8, 20, 9, 15, 22)));
+
+        clock.advance(chrono::Duration::seconds(18));
+        publisher.tick();
+        assert_eq!(publisher.shared.status().second, 0);
+        assert_eq!(publisher.shared.status().width, None);
+    }
+
+    #[test]
+    fn wake_error_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 22));
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+        clock.advance(chrono::Duration::microseconds(1500));
+        publisher.tick();
+        assert_eq!(publisher.shared.status().wake_error_us, 1500);
+        // woke up for the next second a little early
+        clock.advance(chrono::Duration::microseconds(997_000));
+        publisher.tick();
+        assert_eq!(publisher.shared.status().wake_error_us, -1500);
+
+        // the update thread on a clock 5ms before the next second, which it
+        // publishes a little late but never early, however loaded the host
+        let next = at(2023, 8, 20, 9, 15, 23);
+        let clock = SimulatedClock::starting_at(next - chrono::Duration::milliseconds(5));
+        let samples = SignalSamples::builder()
+            .clock(clock)
+            .precise_timing(true)
+            .build_samples()
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while samples.status().time.is_none_or(|t| t < next) {
+            assert!(Instant::now() < deadline, "{:?}", samples.status());
+            thread::sleep(Duration::from_millis(1));
+        }
+        let status = samples.status();
+        assert!((0..500_000).contains(&status.wake_error_us), "{status:?}");
+    }
+
+    #[test]
+    fn early_wake_test() {
+        // a clock that stops right before the second, as a thread woken up
+        // early sees it
+        let next = at(2023, 8, 20, 9, 15, 7);
+        let clock = MockClock::new(next - chrono::Duration::microseconds(100));
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+        assert!(!publisher.wake(next));
+        assert_eq!(publisher.shared.generation.load(Ordering::SeqCst), 0);
+
+        // published once the clock gets there
+        clock.advance(chrono::Duration::microseconds(100));
+        assert!(publisher.wake(next));
+        assert_eq!(publisher.shared.status().second, 7);
+    }
+
+    #[test]
+    fn clock_jump_test() {
+        let start = at(2023, 8, 20, 9, 15, 0);
+        let clock = MockClock::new(start);
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+        let mut tick = |t: ZonedDateTime| {
+            clock.set(t);
+            publisher.tick();
+            publisher.shared.status()
+        };
+        for s in 0..=10 {
+            tick(start + chrono::Duration::seconds(s));
+        }
+        // stepped back by 20s, onto the middle of the previous frame
+        let back = start - chrono::Duration::seconds(10);
+        let status = tick(back);
+        assert_eq!((status.time, status.second), (Some(back), 10));
+        for s in -9..=20 {
+            tick(start + chrono::Duration::seconds(s));
+        }
+        // and forward by an hour, the frame around the jump is not whole
+        let status = tick(start + chrono::Duration::seconds(3621));
+        assert_eq!(status.second, 1);
+        for s in 3622..=3640 {
+            tick(start + chrono::Duration::seconds(s));
+        }
+        // only the frame after the backward step completed
+        assert_eq!(tick(start + chrono::Duration::seconds(3641)).frames, 1);
+
+        // halfway through a leap second
+        let leap = at(2016, 12, 31, 23, 59, 59)
+            .with_nanosecond(1_500_000_000)
+            .unwrap();
+        assert_eq!(until_next_second(leap), Duration::from_millis(500));
+        assert_eq!(until_next_second(start), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn render_loop_wav_test() {
+        let path = std::env::temp_dir().join("bpcsync_render_loop_wav_test.wav");
+        render_loop_wav(&BPC::new(), &path, at(2023, 8, 20, 9, 15, 7)).unwrap();
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration(), 60 * DEFAULT_SAMPLE_RATE);
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+        // played twice in a row, the seam steps no further than any two
+        // neighbouring samples of the carrier do
+        let steps = samples
+            .windows(2)
+            .map(|w| (w[1] as i32 - w[0] as i32).abs());
+        let seam = (samples[0] as i32 - *samples.last().unwrap() as i32).abs();
+        assert!(seam <= steps.max().unwrap(), "seam of {seam}");
+
+        let bytes = std::fs::read(&path).unwrap();
+        let riff_len = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        assert_eq!(riff_len as usize, bytes.len() - 8);
+        let smpl = &bytes[bytes.len() - 68..];
+        assert_eq!(&smpl[..4], b"smpl");
+        let end = u32::from_le_bytes(smpl[56..60].try_into().unwrap());
+        assert_eq!(end, 60 * DEFAULT_SAMPLE_RATE - 1);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loop_carrier(13700.), 13700.);
+        let carrier = loop_carrier(68500. / 9.);
+        assert!((carrier * 60. - (carrier * 60.).round()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fade_out_test() {
+        let sr = DEFAULT_SAMPLE_RATE;
+        // halfway through the full carrier of the frame marker
+        let mut wave = SignalSamples::builder()
+            .clock(MockClock::new(at(2023, 8, 20, 9, 15, 0)))
+            .build_samples()
+            .unwrap();
+        let monitor = wave.monitor();
+        while wave.status().time.is_none() {
+            thread::yield_now();
+        }
+        _ = wave.by_ref().take(sr as usize / 2).count();
+        assert!((wave.inner.level - 1.).abs() < 1e-6);
+
+        monitor.fade_out();
+        assert!(!monitor.faded());
+        let len = ramp_samples(FADE_OUT, sr);
+        let levels: Vec<f32> = (0..len)
+            .map(|_| {
+                wave.next();
+                wave.inner.level
+            })
+            .collect();
+        assert!(levels.windows(2).all(|w| w[1] <= w[0]));
+        assert!((levels[len / 2] - 0.5).abs() < 0.01);
+        assert!(monitor.faded());
+        assert!(wave.by_ref().take(sr as usize * 2).all(|s| s == 0.));
+    }
+
+    #[test]
+    fn reset_test() {
+        let sr = DEFAULT_SAMPLE_RATE;
+        let start = at(2023, 8, 20, 9, 15, 2);
+        let mut wave = SignalWave::new(BPC::new(), sr, start);
+        _ = wave.by_ref().take(sr as usize * 3 / 2 + 7).count();
+        wave.reset(start);
+        assert_eq!(wave.time(), start);
+        let fresh = SignalWave::new(BPC::new(), sr, start);
+        assert!(wave.zip(fresh).take(sr as usize).all(|(a, b)| a == b));
+
+        // reset from another thread, the samples start over like new ones
+        let build = || {
+            let samples = SignalSamples::builder()
+                .clock(MockClock::new(start))
+                .build_samples()
+                .unwrap();
+            while samples.status().time.is_none() {
+                thread::yield_now();
+            }
+            samples
+        };
+        let mut samples = build();
+        _ = samples.by_ref().take(sr as usize / 3 + 7).count();
+        let monitor = samples.monitor();
+        thread::spawn(move || monitor.reset()).join().unwrap();
+        // early in the reduction of the second, like the new ones
+        let resumed: Vec<f32> = samples.by_ref().take(1000).collect();
+        assert!(samples.inner.num_samples < width_samples(50, sr));
+        let fresh: Vec<f32> = build().take(1000).collect();
+        assert_eq!(resumed, fresh);
+        assert_eq!(resumed[0], 0.);
+    }
+
+    #[test]
+    fn monitor_test() {
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(MockClock::new(at(2023, 8, 20, 9, 15, 58)))
+            .aligned(true)
+            .parts()
+            .unwrap();
+        let monitor = Monitor(publisher.shared.clone());
+        thread::sleep(Duration::from_millis(20));
+        assert!(monitor.since_last_update() >= Duration::from_millis(20));
+
+        // waiting for the minute still counts as alive: no older than the
+        // tick, the heartbeat being kept in whole ms
+        let before = Instant::now();
+        publisher.tick();
+        let since = monitor.since_last_update();
+        assert!(
+            since <= before.elapsed() + Duration::from_millis(1),
+            "{since:?}"
+        );
+        assert_eq!(monitor.status().time, None);
+    }
+
+    #[test]
+    fn pull_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 2));
+        let (inner, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+        let mut wave = samples(inner, &publisher);
+        let monitor = wave.monitor();
+
+        // published seconds the output never pulls; what just happened is no
+        // older than the call that did it, both being kept in whole ms
+        thread::sleep(Duration::from_millis(20));
+        let before = Instant::now();
+        publisher.tick();
+        let since = monitor.since_last_update();
+        assert!(
+            since <= before.elapsed() + Duration::from_millis(1),
+            "{since:?}"
+        );
+        assert!(monitor.since_last_pull() >= Duration::from_millis(20));
+
+        let before = Instant::now();
+        wave.next();
+        let since = monitor.since_last_pull();
+        assert!(
+            since <= before.elapsed() + Duration::from_millis(1),
+            "{since:?}"
+        );
+    }
+
+    #[test]
+    fn summary_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 0));
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+        for _ in 0..60 {
+            publisher.tick();
+            clock.advance(chrono::Duration::seconds(1));
+        }
+        let summary = &publisher.summary;
+        assert_eq!(summary.widths.values().sum::<u32>(), 60);
+        assert_eq!(
+            summary.to_string(),
+            "100ms x15, 200ms x23, 300ms x7, 400ms x12, no pulse x3, check bits 0 x2 1 x4"
+        );
+
+        // logged and started over with the next minute
+        publisher.tick();
+        assert_eq!(publisher.summary.widths, BTreeMap::from([(None, 1)]));
+        assert_eq!(publisher.summary.to_string(), "no pulse x1");
+    }
+
+    #[test]
+    fn frames_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 15));
+        let (_, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+        let mut run = |seconds| {
+            for _ in 0..seconds {
+                publisher.tick();
+                clock.advance(chrono::Duration::seconds(1));
+            }
+            publisher.shared.status().frames
+        };
+
+        // the frame already underway does not count
+        assert_eq!(run(6), 0);
+        assert_eq!(run(19), 0);
+        assert_eq!(run(1), 1);
+        assert_eq!(run(20), 2);
+
+        // a skipped second spoils the frame
+        clock.advance(chrono::Duration::seconds(1));
+        assert_eq!(run(19), 2);
+        assert_eq!(run(20), 3);
+    }
+
+    #[test]
+    fn drift_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 2));
+        let (inner, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .parts()
+            .unwrap();
+        let mut wave = samples(inner, &publisher);
+        let mut second = |samples| {
+            publisher.tick();
+            clock.advance(chrono::Duration::seconds(1));
+            wave.by_ref().take(samples).count();
+            wave.status().drift
+        };
+
+        // the first second starts wherever the output happens to be
+        assert_eq!(second(44110), 0);
+        assert_eq!(second(44090), 10);
+        assert_eq!(second(44100), 0);
+        assert_eq!(second(1), 0);
+    }
+
+    #[test]
+    fn publish_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 2));
+        let (inner, mut publisher) = SignalSamples::builder()
+            .clock(clock)
+            .ramp(Duration::ZERO)
+            .parts()
+            .unwrap();
+        let mut wave = samples(inner, &publisher);
+
+        publisher.tick();
+        assert!(wave.by_ref().take(4410).all(|s| s == 0.));
+        assert!(wave.by_ref().take(4410).any(|s| s != 0.));
+        assert_eq!(wave.inner.gate, Gate::Pulse(4410));
+    }
+
+    #[test]
+    fn stalled_test() {
+        let (inner, publisher) = SignalSamples::builder()
+            .ramp(Duration::ZERO)
+            .fade_in(Duration::ZERO)
+            .parts()
+            .unwrap();
+        let mut wave = samples(inner, &publisher);
+        wave.thread = Some(thread::spawn(|| -> Publisher {
+            panic!("update thread panic")
+        }));
+        while !wave.thread.as_ref().unwrap().is_finished() {
+            thread::yield_now();
+        }
+        // died halfway through publishing a pulse, which is never handed over
+        wave.shared
+            .slot(1)
+            .gate
+            .store(Gate::Pulse(4410).to_bits(), Ordering::SeqCst);
+
+        // steady carrier once the next second is late
+        assert!(wave.by_ref().take(2 * 44100).all(|s| s != 0.));
+        assert!(wave.stalled);
+        assert_eq!(wave.inner.gate, Gate::Marker);
+    }
+
+    #[test]
+    fn modulation_depth_test() {
+        let peak = |samples: &[f32]| samples.iter().fold(0f32, |m, s| m.max(s.abs()));
+        for (depth, low) in [(0., 1.), (0.5, 0.5), (1., 0.)] {
+            let mut inner = Oscillator::new(DEFAULT_CARRIER, DEFAULT_SAMPLE_RATE);
+            inner.depth = depth;
+            inner.set_ramp(Duration::ZERO);
+            inner.set_gate(Gate::Pulse(4410));
+            let samples: Vec<f32> = inner.take(8820).collect();
+            assert!((peak(&samples[..4410]) - low).abs() < 1e-3, "depth {depth}");
+            assert!((peak(&samples[4410..]) - 1.).abs() < 1e-3, "depth {depth}");
+        }
+        let builder = SignalSamples::builder().modulation_depth(1.5);
+        assert!(builder.build_samples().is_err());
+    }
+
+    #[test]
+    fn waveform_test() {
+        let mut inner = Oscillator::new(DEFAULT_CARRIER, DEFAULT_SAMPLE_RATE);
+        inner.waveform = Waveform::Square;
+        inner.gain = 0.5;
+        inner.set_ramp(Duration::ZERO);
+        inner.set_gate(Gate::Pulse(4410));
+        assert!(inner.by_ref().take(4410).all(|s| s == 0.));
+        assert!(inner.take(44100 - 4410).all(|s| s == 0.5 || s == -0.5));
+    }
+
+    #[test]
+    fn channels_test() {
+        let mut wave = SignalSamples::builder()
+            .channels(2)
+            .channel_phase(180.)
+            .build_samples()
+            .unwrap();
+        assert_eq!(wave.channels(), 2);
+        // left then right of each frame
+        for _ in 0..1000 {
+            let left = wave.next().unwrap();
+            let right = wave.next().unwrap();
+            assert!((left + right).abs() < 1e-6);
+        }
+        assert!(SignalSamples::builder()
+            .channels(0)
+            .build_samples()
+            .is_err());
+    }
+
+    #[test]
+    fn signal_wave_test() {
+        let mut wave = SignalWave::new(BPC::new(), DEFAULT_SAMPLE_RATE, at(2023, 8, 20, 9, 15, 2))
+            .with_ramp(Duration::ZERO);
+        // reserved, 100ms
+        assert!(wave.by_ref().take(4410).all(|s| s == 0.));
+        assert!(wave.by_ref().take(44100 - 4410).all(|s| s != 0.));
+        // hour high of 9 o'clock is 0b10, 300ms
+        assert!(wave.by_ref().take(13230).all(|s| s == 0.));
+        assert_eq!(wave.time(), at(2023, 8, 20, 9, 15, 3));
+
+        // 200ms for a zero at the first second of the wwvb minute
+        let t = at(2023, 8, 20, 9, 15, 58);
+        let mut wave =
+            SignalWave::new(crate::WWVB::new(), DEFAULT_SAMPLE_RATE, t).with_ramp(Duration::ZERO);
+        assert_eq!(wave.nth(3 * 44100), Some(0.));
+        // last reduced sample, then the carrier is back
+        assert_eq!(wave.nth(8820 - 2), Some(0.));
+        assert!(wave.next().unwrap() != 0.);
+    }
+
+    #[test]
+    fn edge_test() {
+        // from halfway through 09:15:05, so that the recording starts on
+        // neither edge of a reduction
+        let sr = DEFAULT_SAMPLE_RATE;
+        let half = sr as usize / 2;
+        let wave = SignalWave::new(BPC::new(), sr, at(2023, 8, 20, 9, 15, 5));
+        let samples: Vec<f32> = wave.skip(half).take(3 * sr as usize).collect();
+        let recording = crate::analysis::recorded_widths(&samples, sr).unwrap();
+
+        // the reduction of 09:15:06 starts right on its edge, half a second
+        // in, and lasts its 400ms from there
+        assert!(recording.offset.abs_diff(half) <= 2 * sr as usize / 1000);
+        for width in &recording.widths[..2] {
+            assert!(width.is_some_and(|w| w.abs_diff(400) <= 2), "{width:?}");
+        }
+        let edge = recording.offset;
+        let peak =
+            |range: std::ops::Range<usize>| samples[range].iter().fold(0f32, |m, s| m.max(s.abs()));
+        let ms = sr as usize / 1000;
+        assert!(peak(edge - 10 * ms..edge) > 0.99);
+        assert!(peak(edge + 5 * ms..edge + 395 * ms) < 0.01);
+        assert!(peak(edge + 405 * ms..edge + 995 * ms) > 0.99);
+    }
+
+    #[test]
+    fn inverted_test() {
+        for gate in [Gate::Marker, Gate::Pulse(4410), Gate::High(8820)] {
+            assert_eq!(Gate::from_bits(gate.to_bits()), gate);
+        }
+
+        // a jjy zero keeps the carrier for 800ms, then reduces it
+        let t = at(2023, 8, 20, 9, 15, 4);
+        let mut wave =
+            SignalWave::new(crate::JJY::new(), DEFAULT_SAMPLE_RATE, t).with_ramp(Duration::ZERO);
+        assert!(wave.by_ref().take(35280).all(|s| s != 0.));
+        assert!(wave.by_ref().take(8820).all(|s| s == 0.));
+        assert!(wave.next().unwrap() != 0.);
+    }
+
+    #[test]
+    fn idle_test() {
+        let sr = DEFAULT_SAMPLE_RATE as usize;
+        let peak = |idle| {
+            let (mut inner, _) = SignalSamples::builder()
+                .idle(idle)
+                .modulation_depth(0.8)
+                .parts()
+                .unwrap();
+            let mut peaks = Vec::new();
+            for gate in [Gate::Marker, Gate::Pulse(sr / 10)] {
+                inner.set_gate(gate);
+                // the first 50ms, past the ramp from the previous second
+                let second: Vec<f32> = inner.by_ref().take(sr).collect();
+                let peak = second[sr / 100..sr / 20]
+                    .iter()
+                    .fold(0f32, |m, s| m.max(s.abs()));
+                peaks.push(peak);
+            }
+            peaks
+        };
+        let close = |a: Vec<f32>, b: [f32; 2]| {
+            assert!(
+                a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.01),
+                "{a:?} {b:?}"
+            )
+        };
+        // the pulses of the other seconds keep the modulation depth
+        close(peak(Idle::FullCarrier), [1., 0.2]);
+        close(peak(Idle::Silence), [0., 0.2]);
+        close(peak(Idle::ReducedCarrier(0.5)), [0.5, 0.2]);
+
+        assert_eq!(
+            SignalSamples::builder()
+                .idle(Idle::ReducedCarrier(1.5))
+                .build_samples()
+                .err(),
+            Some(BuildError::InvalidIdle(1.5))
+        );
+    }
+
+    #[test]
+    fn comb_test() {
+        let carriers = vec![68500. / 5., 68500. / 7., 68500. / 9.];
+        let (mut inner, _) = SignalSamples::builder()
+            .carriers(carriers.clone())
+            .parts()
+            .unwrap();
+        assert_eq!(inner.carrier, carriers[0]);
+        let samples: Vec<f32> = inner.by_ref().take(DEFAULT_SAMPLE_RATE as usize).collect();
+        assert!(samples.iter().all(|s| (-1. ..=1.).contains(s)));
+        // the tones line up now and then, but never add up past full scale
+        let peak = samples.iter().fold(0f32, |m, s| m.max(s.abs()));
+        assert!(peak > 0.9 && peak <= 1., "peak {peak}");
+
+        let (mut square, _) = SignalSamples::builder()
+            .carriers(carriers[..2].to_vec())
+            .waveform(Waveform::Square)
+            .parts()
+            .unwrap();
+        assert!(square.by_ref().take(1000).all(|s| (-1. ..=1.).contains(&s)));
+
+        assert!(SignalSamples::builder()
+            .carriers(vec![13700., 30000.])
+            .build_samples()
+            .is_err());
+    }
+
+    #[test]
+    fn carrier_frequency_test() {
+        // the full carrier for a minute, the length of a bpc frame
+        let start = at(2023, 8, 20, 9, 15, 0);
+        for (carrier, sample_rate) in [
+            (DEFAULT_CARRIER, DEFAULT_SAMPLE_RATE),
+            (DEFAULT_CARRIER, 48000),
+            (68500. / 9., DEFAULT_SAMPLE_RATE),
+        ] {
+            let wave =
+                SignalWave::new(TestPattern::Steady, sample_rate, start).with_carrier(carrier);
+            let samples: Vec<f32> = wave.take(sample_rate as usize * 60).collect();
+            let rising = samples
+                .windows(2)
+                .filter(|w| w[0] < 0. && w[1] >= 0.)
+                .count();
+            // counting whole cycles is off by one at most, 1/60 Hz over the
+            // minute; the phase is accumulated, so neither the ratio of the
+            // carrier to the sample rate nor the start of each second adds
+            // any error on top
+            let expected = carrier as f64 * 60.;
+            assert!(
+                (rising as f64 - expected).abs() <= 1.,
+                "{rising} cycles of {carrier} Hz at {sample_rate} Hz"
+            );
+        }
+    }
+
+    #[test]
+    fn peak_test() {
+        let build = |gain, normalize| {
+            SignalSamples::builder()
+                .carriers(vec![68500. / 5., 68500. / 7.])
+                .waveform(Waveform::Square)
+                .fade_in(Duration::ZERO)
+                .gain(gain)
+                .normalize(normalize)
+                .parts()
+                .unwrap()
+                .0
+        };
+        // the estimate holds for the samples
+        let mut quiet = build(0.8, false);
+        assert_eq!(quiet.peak(), 0.8);
+        let peak = quiet.by_ref().take(1000).fold(0f32, |m, s| m.max(s.abs()));
+        assert!(peak <= 0.8 && peak > 0.7, "peak {peak}");
+
+        // a gain that clips is kept unless normalized, within the clamp anyway
+        let mut loud = build(2., false);
+        assert_eq!(loud.gain, 2.);
+        assert!(loud.by_ref().take(1000).all(|s| (-1. ..=1.).contains(&s)));
+        let normalized = build(-2., true);
+        assert_eq!((normalized.gain, normalized.peak()), (-1., 1.));
+        assert_eq!(build(0.5, true).gain, 0.5);
+    }
+
+    #[test]
+    fn invert_test() {
+        assert_eq!(Gate::Marker.inverse(), Gate::High(0));
+        assert_eq!(Gate::Pulse(4410).inverse().inverse(), Gate::Pulse(4410));
+
+        let sr = DEFAULT_SAMPLE_RATE;
+        let start = at(2023, 8, 20, 9, 15, 0);
+        let clock = MockClock::new(start);
+        let publish = |invert| {
+            let (mut inner, mut publisher) = SignalSamples::builder()
+                .clock(clock.clone())
+                .invert(invert)
+                .fade_in(Duration::ZERO)
+                .parts()
+                .unwrap();
+            (0..20)
+                .flat_map(|_| {
+                    publisher.tick();
+                    clock.advance(chrono::Duration::seconds(1));
+                    inner.set_gate(published(&publisher.shared));
+                    inner.by_ref().take(sr as usize).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let bpc = BPC::new();
+        let width = |i| bpc.signal_width_ms(start + chrono::Duration::seconds(i as i64));
+
+        // reduced first: the detector recovers the frame
+        let samples = publish(false);
+        let widths = crate::analysis::pulse_widths(&samples, sr);
+        // to within the 1ms the detector measures in
+        for (i, measured) in widths.into_iter().enumerate() {
+            let close = measured.zip(width(i)).map(|(m, w)| m.abs_diff(w) <= 2);
+            assert!(close.unwrap_or(measured == width(i)), "fragment {i}");
+        }
+
+        // carrier first: it lasts the symbol width, then the second is reduced
+        clock.set(start);
+        let samples = publish(true);
+        for (i, second) in samples.chunks_exact(sr as usize).enumerate() {
+            // past the ramp up from the reduced end of the previous second
+            let carrier = second
+                .chunks(sr as usize / 1000)
+                .skip(5)
+                .position(|w| w.iter().all(|s| s.abs() < 0.5));
+            let ms = carrier.map(|ms| (((ms + 5) as f64 / 100.).round() * 100.) as u32);
+            assert_eq!(ms, Some(width(i).unwrap_or(0)), "second {i}");
+        }
+    }
+
+    #[test]
+    fn ramp_test() {
+        let mut inner = Oscillator::new(DEFAULT_CARRIER, DEFAULT_SAMPLE_RATE);
+        let ramp = inner.ramp;
+        assert_eq!(ramp, 88);
+        inner.update(&BPC::new(), at(2023, 8, 20, 9, 15, 2));
+
+        let mut levels = |n| {
+            (0..n)
+                .map(|_| inner.next().map(|_| inner.level).unwrap())
+                .collect::<Vec<_>>()
+        };
+        // eases down from the full carrier, stays reduced, then eases back up
+        let down = levels(ramp);
+        assert!(down.windows(2).all(|w| w[0] >= w[1]));
+        let reduced = levels(4410 - ramp);
+        assert!(reduced.iter().all(|&l| l == 0.));
+        let up = levels(ramp);
+        assert!(up.windows(2).all(|w| w[0] <= w[1]));
+        assert!(up[0] < 0.01 && up[ramp - 1] > 0.99);
+        assert!(levels(100).iter().all(|&l| l == 1.));
+    }
+
+    #[test]
+    fn marker_test() {
+        let mut inner = Oscillator::new(DEFAULT_CARRIER, DEFAULT_SAMPLE_RATE);
+        inner.set_ramp(Duration::ZERO);
+        inner.update(&BPC::new(), at(2023, 8, 20, 9, 15, 2));
+        assert_eq!(inner.gate, Gate::Pulse(4410));
+        assert!(inner.by_ref().take(4410).all(|s| s == 0.));
+
+        inner.update(&BPC::new(), at(2023, 8, 20, 9, 15, 20));
+        assert_eq!(inner.gate, Gate::Marker);
+        assert!(inner.by_ref().take(4410).any(|s| s != 0.));
+
+        // full carrier for the whole second at the top of the minute
+        inner.update(&BPC::new(), at(2023, 8, 20, 9, 16, 0));
+        assert_eq!(inner.gate, Gate::Marker);
+        let samples: Vec<f32> = inner.take(44100).collect();
+        let mut peaks = samples
+            .chunks(441)
+            .map(|w| w.iter().fold(0f32, |m, s| m.max(s.abs())));
+        assert!(peaks.all(|p| p > 0.99));
+    }
+
+    #[test]
+    fn drop_test() {
+        let wave = SignalSamples::new();
+        let shared = wave.shared.clone();
+        let start = std::time::Instant::now();
+        drop(wave);
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    fn reconfigure_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 2));
+        let mut wave = SignalSamples::new()
+            .with_clock(clock)
+            .with_timezone(FixedOffset::east_opt(0).unwrap());
+        let mut publisher = wave.stop().unwrap();
+        assert_eq!(publisher.timezone, FixedOffset::east_opt(0).unwrap());
+        publisher.tick();
+        assert_eq!(published(&publisher.shared), Gate::Pulse(4410));
+    }
+
+    #[test]
+    fn concurrent_update_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 0));
+        let (inner, mut publisher) = SignalSamples::builder()
+            .clock(clock.clone())
+            .modulation_depth(0.5)
+            .parts()
+            .unwrap();
+        let mut wave = samples(inner, &publisher);
+        let done = Arc::new(AtomicBool::new(false));
+        let hammer = thread::spawn({
+            let done = done.clone();
+            move || {
+                while !done.load(Ordering::SeqCst) {
+                    publisher.tick();
+                    clock.advance(chrono::Duration::seconds(1));
+                }
+            }
+        });
+        // every sample is a step of the carrier, none is held or made up
+        // while a second is being published
+        let mut prev = wave.next().unwrap();
+        let mut seen = 0;
+        while seen < 100 {
+            let generation = wave.generation;
+            for sample in wave.by_ref().take(100) {
+                assert!(sample.is_finite() && sample != prev, "{sample}");
+                prev = sample;
+            }
+            seen += (wave.generation != generation) as u32;
+        }
+        done.store(true, Ordering::SeqCst);
+        hammer.join().unwrap();
+    }
+
+    #[test]
+    fn continuous_phase_test() {
+        let mut inner = Oscillator::new(DEFAULT_CARRIER, DEFAULT_SAMPLE_RATE);
+        let step = inner.phase_step() as f32;
+        let mut prev = inner.nth(1234).unwrap();
+        for _ in 0..3 {
+            // fragment 0 keeps the carrier on for the whole second
+            inner.update(&BPC::new(), at(2023, 8, 20, 9, 15, 20));
+            for _ in 0..100 {
+                let sample = inner.next().unwrap();
+                assert!((sample - prev).abs() <= step + f32::EPSILON);
+                prev = sample;
+            }
+        }
+    }
+
+    #[test]
+    fn check_carrier_test() {
+        assert!(check_carrier(13700., 22050).is_err());
+        assert!(check_carrier(11025., 22050).is_err());
+        assert!(check_carrier(11024., 22050).is_ok());
+        assert!(check_carrier(13700., 44100).is_ok());
+        // past 0.4 of the sample rate only warns
+        assert!(check_carrier(20000., 44100).is_ok());
+        assert!(check_carrier(0., 44100).is_err());
+        assert!(check_carrier(-1., 44100).is_err());
+        assert!(check_carrier(f32::NAN, 44100).is_err());
+        assert!(SignalSamples::builder()
+            .sample_rate(22050)
+            .build_samples()
+            .is_err());
+    }
+
+    #[test]
+    fn pulse_test() {
+        let sr = DEFAULT_SAMPLE_RATE;
+        for (code, width) in crate::CODE_WIDTHS {
+            let second = crate::signal::pulse(code, sr, 12000.).unwrap();
+            assert_eq!(second.len(), sr as usize);
+            let measured = crate::analysis::pulse_widths(&second, sr)[0].unwrap();
+            assert!(measured.abs_diff(width) <= 2, "code {code}: {measured}ms");
+            // eased down from the full carrier, as if after a marker, then
+            // reduced up to the width and full again after the ramp
+            let ramp = ramp_samples(DEFAULT_RAMP, sr);
+            let gap = width_samples(width, sr);
+            assert!(
+                second[ramp..gap].iter().all(|s| s.abs() < 1e-3),
+                "code {code}"
+            );
+            let after = gap + ramp;
+            let peak = second[after..].iter().fold(0f32, |m, s| m.max(s.abs()));
+            assert!(peak > 0.99, "code {code}");
+        }
+        assert!(pulse(0b100, sr, 12000.).is_err());
+        assert!(pulse(0b11, sr, 30000.).is_err());
+    }
+
+    #[test]
+    fn fade_in_test() {
+        let (mut inner, _) = SignalSamples::builder().parts().unwrap();
+        let n = DEFAULT_SAMPLE_RATE as usize / 100;
+        let levels: Vec<f32> = (0..n + 10)
+            .map(|_| {
+                inner.next();
+                inner.level
+            })
+            .collect();
+        assert!(levels[0] < 0.01, "{}", levels[0]);
+        assert!(levels[..n].windows(2).all(|w| w[0] < w[1]));
+        assert!(levels[n..].iter().all(|&l| l == 1.));
+
+        // and only once
+        inner.set_gate(Gate::Pulse(n));
+        inner.by_ref().take(2 * n).count();
+        assert_eq!(inner.level, 1.);
+
+        let (mut instant, _) = SignalSamples::builder()
+            .fade_in(Duration::ZERO)
+            .parts()
+            .unwrap();
+        instant.next();
+        assert_eq!(instant.level, 1.);
+    }
+
+    #[test]
+    fn first_second_test() {
+        let sr = DEFAULT_SAMPLE_RATE as usize;
+        // a 300ms pulse, joined 250ms in on the second start
+        for (start, reduced) in [(0, 300), (250, 50)] {
+            let t = at(2023, 8, 20, 9, 15, 3) + chrono::Duration::milliseconds(start);
+            let samples: Vec<f32> = SignalSamples::builder()
+                .clock(MockClock::new(t))
+                .ramp(Duration::ZERO)
+                .build_samples()
+                .unwrap()
+                .take(sr / 2)
+                .collect();
+            let ms = |ms: usize| ms * sr / 1000;
+            let peak = |s: &[f32]| s.iter().fold(0f32, |m, s| m.max(s.abs()));
+            assert!(peak(&samples[..ms(reduced) - 1]) < 0.01, "from {start}ms");
+            assert!(peak(&samples[ms(reduced) + 1..]) > 0.99, "from {start}ms");
+        }
+    }
+
+    #[test]
+    fn build_error_test() {
+        let error = |builder: BPCWaveBuilder| builder.build_samples().err().unwrap();
+        let builder = SignalSamples::builder;
+        assert_eq!(
+            error(builder().sample_rate(0)),
+            BuildError::InvalidSampleRate(0)
+        );
+        let aliased = error(builder().sample_rate(22050));
+        assert_eq!(
+            aliased,
+            BuildError::InvalidCarrier {
+                carrier: 13700.,
+                sample_rate: 22050
+            }
+        );
+        assert_eq!(
+            aliased.to_string(),
+            "carrier frequency 13700 Hz would alias at a 22050 Hz sample rate, \
+             it must be below 11025 Hz"
+        );
+        assert_eq!(
+            error(builder().carrier(-1.)).to_string(),
+            "carrier frequency -1 Hz must be positive"
+        );
+        assert_eq!(
+            error(builder().gain(f32::NAN)).to_string(),
+            "gain NaN is not finite"
+        );
+        assert_eq!(error(builder().channels(0)), BuildError::NoChannels);
+        assert_eq!(
+            error(builder().modulation_depth(2.)),
+            BuildError::InvalidModulationDepth(2.)
+        );
+        assert_eq!(
+            error(builder().ramp(Duration::from_millis(50))),
+            BuildError::InvalidRamp(Duration::from_millis(50))
+        );
+        assert_eq!(
+            error(builder().offset(Duration::MAX)),
+            BuildError::InvalidOffset(Duration::MAX)
+        );
+        assert_eq!(
+            error(builder().wake_margin(Duration::from_millis(100))),
+            BuildError::InvalidWakeMargin(Duration::from_millis(100))
+        );
+    }
+
+    #[test]
+    fn builder_test() {
+        let clock = MockClock::new(at(2023, 8, 20, 9, 15, 2));
+        let (inner, mut publisher) = SignalSamples::builder()
+            .carrier(68500. / 9.)
+            .sample_rate(48000)
+            .gain(0.5)
+            .timezone(FixedOffset::east_opt(0).unwrap())
+            .clock(clock)
+            .parts()
+            .unwrap();
+        assert_eq!(inner.carrier, 68500. / 9.);
+        assert_eq!(inner.sample_rate, 48000);
+        assert_eq!(inner.gain, 0.5);
+        publisher.tick();
+        assert_eq!(published(&publisher.shared), Gate::Pulse(4800));
+
+        assert!(SignalSamples::builder()
+            .gain(f32::NAN)
+            .build_samples()
+            .is_err());
+        assert!(SignalSamples::builder()
+            .sample_rate(0)
+            .build_samples()
+            .is_err());
+    }
+}
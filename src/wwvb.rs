@@ -0,0 +1,301 @@
+use crate::{TimeSignal, ZonedDateTime};
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{Datelike, FixedOffset, NaiveDate, Timelike, Utc};
+
+const WWVB_FREQ: u32 = 60000;
+// the 3rd subharmonic is the highest one a 44.1khz output can carry
+const DEFAULT_CARRIER: f32 = (WWVB_FREQ / 3) as f32;
+
+/// What is transmitted during one second of a WWVB frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symbol {
+    Zero,
+    One,
+    Marker,
+}
+
+impl Symbol {
+    /// Width in ms of the power reduction at the start of the second.
+    pub fn width(self) -> u32 {
+        match self {
+            Symbol::Zero => 200,
+            Symbol::One => 500,
+            Symbol::Marker => 800,
+        }
+    }
+
+    fn from_width(width: u32) -> Option<Self> {
+        match width {
+            200 => Some(Symbol::Zero),
+            500 => Some(Symbol::One),
+            800 => Some(Symbol::Marker),
+            _ => None,
+        }
+    }
+}
+
+// the fields a frame carries in bcd
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Minute,
+    Hour,
+    Day,
+    Year,
+}
+
+// the bcd field and bit carried by each data second of the frame
+fn position(second: u32) -> Option<(Field, u32)> {
+    match second {
+        1..=3 => Some((Field::Minute, 7 - second)),
+        5..=8 => Some((Field::Minute, 8 - second)),
+        12..=13 => Some((Field::Hour, 17 - second)),
+        15..=18 => Some((Field::Hour, 18 - second)),
+        22..=23 => Some((Field::Day, 31 - second)),
+        25..=28 => Some((Field::Day, 32 - second)),
+        30..=33 => Some((Field::Day, 33 - second)),
+        45..=48 => Some((Field::Year, 52 - second)),
+        50..=53 => Some((Field::Year, 53 - second)),
+        _ => None,
+    }
+}
+
+fn is_marker(second: u32) -> bool {
+    second == 0 || second % 10 == 9
+}
+
+fn bcd(v: u32) -> u32 {
+    ((v / 100) << 8) | ((v / 10 % 10) << 4) | (v % 10)
+}
+
+fn from_bcd(v: u32) -> u32 {
+    (v >> 8) * 100 + ((v >> 4) & 0xf) * 10 + (v & 0xf)
+}
+
+fn is_leap_year(year: i32) -> bool {
+    NaiveDate::from_ymd_opt(year, 2, 29).is_some()
+}
+
+/// The WWVB time code broadcast on 60 kHz from Fort Collins, Colorado.
+///
+/// A frame lasts a minute, one symbol per second, and carries the UTC time of
+/// the minute it starts. DUT1 is always sent as +0.0s and the daylight saving
+/// and leap second bits are left clear.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Default)]
+pub struct WWVB {}
+
+impl WWVB {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Returns the symbol transmitted during the second of `t`.
+    pub fn symbol_at(&self, t: ZonedDateTime) -> Symbol {
+        let t = t.with_timezone(&Utc);
+        let second = t.second();
+        if is_marker(second) {
+            return Symbol::Marker;
+        }
+        let bit = match position(second) {
+            Some((field, bit)) => {
+                let v = match field {
+                    Field::Minute => t.minute(),
+                    Field::Hour => t.hour(),
+                    Field::Day => t.ordinal(),
+                    Field::Year => t.year() as u32 % 100,
+                };
+                (bcd(v) >> bit) & 1 == 1
+            }
+            // positive dut1 sign
+            None if second == 36 || second == 38 => true,
+            None if second == 55 => is_leap_year(t.year()),
+            None => false,
+        };
+        if bit {
+            Symbol::One
+        } else {
+            Symbol::Zero
+        }
+    }
+
+    /// Returns the symbols of the whole minute containing `t`.
+    pub fn frame(&self, t: ZonedDateTime) -> [Symbol; 60] {
+        let start = self.frame_start(t);
+        std::array::from_fn(|i| self.symbol_at(start + chrono::Duration::seconds(i as i64)))
+    }
+
+    /// Recovers the time from the pulse widths of one frame, the inverse of
+    /// [`WWVB::frame`]. The returned time is the start of the minute, in UTC.
+    pub fn decode(widths: &[Option<u32>; 60]) -> Result<ZonedDateTime> {
+        let mut fields = [0u32; 4];
+        let mut leap_year = false;
+        for (i, width) in widths.iter().enumerate() {
+            let second = i as u32;
+            let symbol = match width.and_then(Symbol::from_width) {
+                Some(symbol) => symbol,
+                None => bail!("unexpected width {width:?} at second {i}"),
+            };
+            ensure!(
+                (symbol == Symbol::Marker) == is_marker(second),
+                "misplaced marker at second {i}"
+            );
+            if symbol != Symbol::One {
+                continue;
+            }
+            match position(second) {
+                Some((field, bit)) => fields[field as usize] |= 1 << bit,
+                None if second == 55 => leap_year = true,
+                None => {}
+            }
+        }
+
+        let [minute, hour, day, year] = fields.map(from_bcd);
+        let year = 2000 + year as i32;
+        let date = NaiveDate::from_yo_opt(year, day)
+            .with_context(|| format!("invalid day {day} of {year}"))?;
+        ensure!(
+            is_leap_year(year) == leap_year,
+            "leap year bit does not match {year}"
+        );
+        let t = date
+            .and_hms_opt(hour, minute, 0)
+            .with_context(|| format!("invalid time {hour:02}:{minute:02}"))?;
+        Ok(t.and_utc()
+            .with_timezone(&FixedOffset::east_opt(0).unwrap()))
+    }
+}
+
+impl TimeSignal for WWVB {
+    fn symbol_width(&self, t: ZonedDateTime) -> Option<u32> {
+        Some(self.symbol_at(t).width())
+    }
+
+    fn carrier_hz(&self) -> f32 {
+        DEFAULT_CARRIER
+    }
+
+    fn frame_len_secs(&self) -> u32 {
+        60
+    }
+
+    /// Describes what is transmitted during the second of `t`, e.g.
+    /// `01:15:03 [ 3] minute = One (500ms)`.
+    fn describe(&self, t: ZonedDateTime) -> String {
+        let second = t.second();
+        let name = match (second, position(second)) {
+            (s, _) if is_marker(s) => "marker",
+            (_, Some((Field::Minute, _))) => "minute",
+            (_, Some((Field::Hour, _))) => "hour",
+            (_, Some((Field::Day, _))) => "day of year",
+            (_, Some((Field::Year, _))) => "year",
+            (36..=38, None) => "dut1 sign",
+            (40..=43, None) => "dut1",
+            (55, None) => "leap year",
+            (56, None) => "leap second",
+            (57..=58, None) => "dst",
+            _ => "unused",
+        };
+        let time = t.with_timezone(&Utc).format("%H:%M:%S");
+        let symbol = self.symbol_at(t);
+        format!(
+            "{time} [{second:>2}] {name} = {symbol:?} ({}ms)",
+            symbol.width()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> ZonedDateTime {
+        FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(y, mo, d, h, mi, s)
+            .unwrap()
+    }
+
+    fn symbols(s: &str) -> Vec<Symbol> {
+        s.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                '0' => Symbol::Zero,
+                '1' => Symbol::One,
+                _ => Symbol::Marker,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reference_frame_test() {
+        let wwvb = WWVB::new();
+        // day 232, not a leap year
+        let frame = wwvb.frame(utc(2023, 8, 20, 1, 15, 0));
+        let expected = "M00100101M 000000001M 001000011M 001000101M 000000010M 001100000M";
+        assert_eq!(frame.to_vec(), symbols(expected));
+
+        // day 60 of a leap year
+        let frame = wwvb.frame(utc(2024, 2, 29, 15, 42, 30));
+        let expected = "M10000010M 000100101M 000000110M 000000101M 000000010M 010001000M";
+        assert_eq!(frame.to_vec(), symbols(expected));
+    }
+
+    #[test]
+    fn timezone_test() {
+        let wwvb = WWVB::new();
+        let cst = FixedOffset::east_opt(8 * 3600).unwrap();
+        let t = cst.with_ymd_and_hms(2023, 8, 20, 9, 15, 0).unwrap();
+        assert_eq!(wwvb.frame(t), wwvb.frame(utc(2023, 8, 20, 1, 15, 0)));
+    }
+
+    fn widths(wwvb: &WWVB, t: ZonedDateTime) -> [Option<u32>; 60] {
+        wwvb.frame(t).map(|s| Some(s.width()))
+    }
+
+    #[test]
+    fn decode_test() {
+        let wwvb = WWVB::new();
+        for t in [
+            utc(2023, 8, 20, 1, 15, 0),
+            utc(2023, 12, 31, 23, 59, 59),
+            utc(2024, 2, 29, 15, 42, 30),
+            utc(2000, 1, 1, 0, 0, 0),
+        ] {
+            let start = wwvb.frame_start(t);
+            assert_eq!(WWVB::decode(&widths(&wwvb, t)).unwrap(), start);
+        }
+    }
+
+    #[test]
+    fn decode_error_test() {
+        let wwvb = WWVB::new();
+        let valid = widths(&wwvb, utc(2023, 8, 20, 1, 15, 0));
+
+        let mut w = valid;
+        w[3] = Some(300);
+        assert!(WWVB::decode(&w).is_err());
+
+        let mut w = valid;
+        w[9] = Some(200);
+        assert!(WWVB::decode(&w).is_err());
+
+        // claims a leap year
+        let mut w = valid;
+        w[55] = Some(500);
+        assert!(WWVB::decode(&w).is_err());
+    }
+
+    #[test]
+    fn describe_test() {
+        let wwvb = WWVB::new();
+        assert_eq!(
+            wwvb.describe(utc(2023, 8, 20, 1, 15, 3)),
+            "01:15:03 [ 3] minute = One (500ms)"
+        );
+        assert_eq!(
+            wwvb.describe(utc(2023, 8, 20, 1, 15, 59)),
+            "01:15:59 [59] marker = Marker (800ms)"
+        );
+    }
+}